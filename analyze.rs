@@ -0,0 +1,246 @@
+//! Analysis of a function on an interval.
+//! The zeros of the first derivative are found by sampling it for sign changes and refining
+//! them with the root finder, then classified with the second derivative.
+//! Poles of the function or its derivative, where it is infinite, are reported as discontinuities.
+
+use std::cmp::{Less, Equal, Greater};
+
+use func::DiffFunc;
+use simplify::derivative_n;
+use solver::{newton, crossing, Root, Pole};
+
+/// The number of subintervals in which the first derivative is sampled.
+static SAMPLES: uint = 200;
+
+/// The second derivative at a critical point below which its sign is not trusted.
+static CURVATURE_TOLERANCE: f64 = 1e-9;
+
+/// The kind of a critical point.
+pub enum Kind {
+    Minimum,
+    Maximum,
+    
+    /// A stationary inflection point, like 0 for x^3.
+    Inflection
+}
+
+/// A zero of the first derivative.
+pub struct CriticalPoint {
+    x: f64,
+    value: f64,
+    kind: Kind
+}
+
+/// How a function changes between critical points.
+#[deriving(Eq)]
+pub enum Direction {
+    Increasing,
+    Decreasing,
+    Flat
+}
+
+/// An interval on which a function is monotonic.
+pub struct Interval {
+    from: f64,
+    to: f64,
+    direction: Direction
+}
+
+/// The critical points, discontinuities and monotonic intervals of a function, all in increasing order.
+pub struct Analysis {
+    critical_points: ~[CriticalPoint],
+    discontinuities: ~[f64],
+    intervals: ~[Interval]
+}
+
+impl ToStr for Kind {
+    fn to_str(&self) -> ~str {
+        match *self {
+            Minimum => ~"Minimum",
+            Maximum => ~"Maximum",
+            Inflection => ~"Inflection point"
+        }
+    }
+}
+
+impl ToStr for Direction {
+    fn to_str(&self) -> ~str {
+        match *self {
+            Increasing => ~"Increasing",
+            Decreasing => ~"Decreasing",
+            Flat => ~"Constant"
+        }
+    }
+}
+
+/// Analyzes a function of x between a and b.
+pub fn analyze(f: &DiffFunc, a: f64, b: f64) -> Result<Analysis, ~str> {
+    if !(a < b) {
+        return Err(format!("Expected an interval from a lower to a higher point, got {} to {}.", a, b))
+    }
+    
+    let df = derivative_n(f, "x", 1);
+    if df.is_zero() {
+        return Ok(Analysis {
+            critical_points: ~[],
+            discontinuities: ~[],
+            intervals: ~[Interval { from: a, to: b, direction: Flat }]
+        })
+    }
+    
+    let d2f = derivative_n(f, "x", 2);
+    let step = (b - a) / SAMPLES as f64;
+    let (roots, discontinuities) = stationary_points(f, &df, a, b);
+    let critical_points = roots.iter().map(|&x|
+        CriticalPoint { x: x, value: f.eval(x), kind: classify(&df, &d2f, x, step / 100.0) }
+    ).collect();
+    let intervals = monotonic_intervals(&df, a, b, roots.as_slice(), discontinuities.as_slice());
+    Ok(Analysis { critical_points: critical_points, discontinuities: discontinuities, intervals: intervals })
+}
+
+/// Finds the zeros of the first derivative between a and b, and the discontinuities.
+fn stationary_points(f: &DiffFunc, df: &DiffFunc, a: f64, b: f64) -> (~[f64], ~[f64]) {
+    let step = (b - a) / SAMPLES as f64;
+    let xs: ~[f64] = range(0, SAMPLES + 1).map(|i| if i == SAMPLES { b } else { a + step * i as f64 }).collect();
+    let ys: ~[f64] = xs.iter().map(|&x| df.eval(x)).collect();
+    
+    let mut roots = ~[];
+    let mut discontinuities = ~[];
+    for i in range(0, SAMPLES + 1) {
+        if ys[i].is_infinite() || f.eval(xs[i]).is_infinite() {
+            push_point(&mut discontinuities, xs[i], step);
+        } else if ys[i] == 0.0 {
+            push_point(&mut roots, xs[i], step);
+        } else if i < SAMPLES && ys[i] * ys[i + 1] < 0.0 {
+            // The derivative also changes sign at a pole, like 0 for ln|x|.
+            match crossing(df, xs[i], xs[i + 1]) {
+                Ok(Root(x)) if f.eval(x).is_finite() => push_point(&mut roots, x, step),
+                Ok(Root(x)) | Ok(Pole(x)) => push_point(&mut discontinuities, x, step),
+                Err(_) => ()
+            }
+        } else if i > 0 && i < SAMPLES && ys[i - 1] * ys[i] > 0.0 && ys[i] * ys[i + 1] > 0.0 &&
+                  ys[i].abs() < ys[i - 1].abs() && ys[i].abs() <= ys[i + 1].abs() {
+            // The derivative may touch zero without changing sign, like 3x^2.
+            match newton(df, xs[i]) {
+                Ok(x) if x >= xs[i - 1] && x <= xs[i + 1] && f.eval(x).is_finite() => push_point(&mut roots, x, step),
+                _ => ()
+            }
+        }
+    }
+    (roots, discontinuities)
+}
+
+/// Adds a point unless it was already found from a neighbouring sample.
+fn push_point(points: &mut ~[f64], x: f64, step: f64) {
+    let n = points.len();
+    if n == 0 || (x - points[n - 1]).abs() > step * 1e-6 {
+        points.push(x);
+    }
+}
+
+/// Classifies a critical point by the sign of the second derivative, or by the signs of the
+/// first derivative on either side where the second derivative vanishes, like for x^4.
+fn classify(df: &DiffFunc, d2f: &DiffFunc, x: f64, h: f64) -> Kind {
+    let curvature = d2f.eval(x);
+    if curvature > CURVATURE_TOLERANCE {
+        Minimum
+    } else if curvature < -CURVATURE_TOLERANCE {
+        Maximum
+    } else {
+        let (left, right) = (df.eval(x - h), df.eval(x + h));
+        if left < 0.0 && right > 0.0 {
+            Minimum
+        } else if left > 0.0 && right < 0.0 {
+            Maximum
+        } else {
+            Inflection
+        }
+    }
+}
+
+/// Splits the interval at the critical points and discontinuities and finds the direction on each piece.
+fn monotonic_intervals(df: &DiffFunc, a: f64, b: f64, roots: &[f64], discontinuities: &[f64]) -> ~[Interval] {
+    let mut cuts = roots.to_owned();
+    cuts.push_all(discontinuities);
+    cuts.sort_by(|&x, &y| if x < y { Less } else if x > y { Greater } else { Equal });
+    
+    let mut bounds = ~[a];
+    for &x in cuts.iter() {
+        if x > a && x < b {
+            bounds.push(x);
+        }
+    }
+    bounds.push(b);
+    
+    let mut intervals: ~[Interval] = ~[];
+    for i in range(0, bounds.len() - 1) {
+        let (l, r) = (bounds[i], bounds[i + 1]);
+        let slope = df.eval((l + r) / 2.0);
+        if slope.is_nan() {
+            // The derivative is not defined here.
+            continue
+        }
+        let direction = if slope > 0.0 { Increasing } else if slope < 0.0 { Decreasing } else { Flat };
+        
+        // Pieces on either side of an inflection point are joined, but not across a discontinuity.
+        let n = intervals.len();
+        if n > 0 && intervals[n - 1].direction == direction && intervals[n - 1].to == l && !discontinuities.contains(&l) {
+            intervals[n - 1].to = r;
+        } else {
+            intervals.push(Interval { from: l, to: r, direction: direction });
+        }
+    }
+    intervals
+}
+
+#[cfg(test)]
+mod test {
+    use func::{Power, Ln, Abs, Compose};
+    use number::Number;
+    use super::{analyze, Minimum, Inflection, Increasing, Decreasing};
+    
+    #[test]
+    fn cube_has_a_stationary_inflection_point() {
+        let analysis = analyze(&Power(Number::int(3)), -2.0, 2.0).unwrap();
+        assert_eq!(analysis.critical_points.len(), 1);
+        let p = &analysis.critical_points[0];
+        assert!(p.x.abs() < 1e-6);
+        assert!(match p.kind { Inflection => true, _ => false });
+        
+        // The pieces on both sides are joined.
+        assert_eq!(analysis.intervals.len(), 1);
+        assert!(analysis.intervals[0].direction == Increasing);
+    }
+    
+    #[test]
+    fn fourth_power_has_a_flat_minimum() {
+        let analysis = analyze(&Power(Number::int(4)), -1.0, 1.0).unwrap();
+        assert_eq!(analysis.critical_points.len(), 1);
+        assert!(match analysis.critical_points[0].kind { Minimum => true, _ => false });
+        assert_eq!(analysis.intervals.len(), 2);
+        assert!(analysis.intervals[0].direction == Decreasing);
+        assert!(analysis.intervals[1].direction == Increasing);
+    }
+    
+    #[test]
+    fn pole_is_a_discontinuity() {
+        let analysis = analyze(&Power(Number::int(-1)), -1.0, 1.0).unwrap();
+        assert!(analysis.critical_points.is_empty());
+        assert_eq!(analysis.discontinuities.len(), 1);
+        assert!(analysis.discontinuities[0].abs() < 1e-6);
+        
+        // 1/x decreases on both sides, but the pieces are not joined across the pole.
+        assert_eq!(analysis.intervals.len(), 2);
+        assert!(analysis.intervals.iter().all(|i| i.direction == Decreasing));
+    }
+    
+    #[test]
+    fn pole_of_the_derivative_is_a_discontinuity() {
+        // The derivative 1/x of ln|x| changes sign at 0, between two samples.
+        let f = Compose { outer: ~Ln, inner: ~Abs };
+        let analysis = analyze(&f, -1.0, 1.3).unwrap();
+        assert!(analysis.critical_points.is_empty());
+        assert_eq!(analysis.discontinuities.len(), 1);
+        assert!(analysis.discontinuities[0].abs() < 1e-6);
+    }
+}
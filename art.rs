@@ -0,0 +1,273 @@
+//! Two-dimensional Unicode rendering, with stacked fractions and raised exponents.
+
+use std::cmp;
+use std::vec;
+
+use func;
+use pretty::{Expr, Num, Sym, Neg, Add, Sub, Mul, Div, Pow, Call, Cases, ATOM};
+
+/// A rectangular block of text with a baseline.
+#[deriving(Clone)]
+struct Block {
+    lines: ~[~str],
+    baseline: uint
+}
+
+/// Renders an expression as multi-line text.
+pub fn to_art(e: &Expr) -> ~str {
+    let block = layout(e);
+    let lines: ~[&str] = block.lines.iter().map(|l| l.trim_right()).collect();
+    lines.connect("\n")
+}
+
+/// Lays out an expression.
+fn layout(e: &Expr) -> Block {
+    match *e {
+        Num(ref n) => Block::text(n.to_str().as_slice()),
+        Sym(ref s) => Block::text(if s.as_slice() == "pi" { "π" } else { s.as_slice() }),
+        Neg(ref a) => Block::beside([Block::text("-"), operand(e, &**a, false)]),
+        Add(ref a, ref b) => binary(e, &**a, " + ", &**b),
+        Sub(ref a, ref b) => binary(e, &**a, " - ", &**b),
+        Mul(ref a, ref b) => binary(e, &**a, " · ", &**b),
+        
+        // Fractions do not need brackets.
+        Div(ref a, ref b) => Block::fraction(layout(&**a), layout(&**b)),
+        
+        // Exponents do not need brackets.
+        Pow(ref a, ref b) => Block::power(operand(e, &**a, false), layout(&**b)),
+        
+        Call(ref name, ref args) => call(name.as_slice(), args.as_slice()),
+        
+        Cases(ref cases, ref default) => {
+            let mut rows: ~[Block] = cases.iter().map(|c| Block::beside([
+                layout(&c.value),
+                Block::text("  if "),
+                layout(&c.left),
+                Block::text(format!(" {} ", relation(c.relation)).as_slice()),
+                layout(&c.right)
+            ])).collect();
+            for d in default.iter() {
+                rows.push(Block::beside([layout(&**d), Block::text("  otherwise")]));
+            }
+            let body = Block::above(rows);
+            Block::beside([Block::brace(body.lines.len(), body.baseline), Block::text(" "), body])
+        }
+    }
+}
+
+/// Lays out a binary operation.
+fn binary(parent: &Expr, left: &Expr, op: &str, right: &Expr) -> Block {
+    Block::beside([operand(parent, left, false), Block::text(op), operand(parent, right, true)])
+}
+
+/// Lays out an operand, with brackets if needed.
+fn operand(parent: &Expr, e: &Expr, right: bool) -> Block {
+    if parent.needs_brackets(e, right) {
+        Block::brackets(layout(e))
+    } else {
+        layout(e)
+    }
+}
+
+/// Lays out a function call.
+fn call(name: &str, args: &[Expr]) -> Block {
+    let last = layout(&args[args.len() - 1]);
+    let atomic = args[args.len() - 1].precedence() == ATOM;
+    
+    match name {
+        "exp"  => Block::power(Block::text("e"), last),
+        "sqrt" => Block::beside([Block::text("√"), if atomic { last } else { Block::brackets(last) }]),
+        "abs"  => {
+            let bar = Block::bar(last.lines.len(), last.baseline);
+            Block::beside([bar.clone(), last, bar])
+        },
+        _ => {
+            // Separate the arguments with commas.
+            let mut parts = ~[];
+            for (i, a) in args.iter().enumerate() {
+                if i > 0 {
+                    parts.push(Block::text(", "));
+                }
+                parts.push(layout(a));
+            }
+            Block::beside([Block::text(name), Block::brackets(Block::beside(parts))])
+        }
+    }
+}
+
+/// Renders a relation.
+fn relation(r: func::Relation) -> &'static str {
+    match r {
+        func::Lt => "<",
+        func::Le => "≤",
+        func::Gt => ">",
+        func::Ge => "≥"
+    }
+}
+
+/// Converts a string to superscript characters, if they all have one.
+fn superscript(s: &str) -> Option<~str> {
+    let mut result = ~"";
+    for ch in s.chars() {
+        let sup = match ch {
+            '0' => '⁰', '1' => '¹', '2' => '²', '3' => '³', '4' => '⁴',
+            '5' => '⁵', '6' => '⁶', '7' => '⁷', '8' => '⁸', '9' => '⁹',
+            '+' => '⁺', '-' => '⁻', '(' => '⁽', ')' => '⁾',
+            'n' => 'ⁿ', 'i' => 'ⁱ', 'x' => 'ˣ', 'y' => 'ʸ',
+            _ => return None
+        };
+        result.push_char(sup);
+    }
+    Some(result)
+}
+
+/// Pads a string with spaces to the given width.
+fn pad(s: &str, width: uint) -> ~str {
+    format!("{}{}", s, " ".repeat(width - s.char_len()))
+}
+
+/// Centers a string within the given width.
+fn center(s: &str, width: uint) -> ~str {
+    let left = (width - s.char_len()) / 2;
+    pad(format!("{}{}", " ".repeat(left), s).as_slice(), width)
+}
+
+impl Block {
+    /// A single line of text.
+    fn text(s: &str) -> Block {
+        Block { lines: ~[s.to_owned()], baseline: 0 }
+    }
+    
+    /// Returns the width of the block.
+    fn width(&self) -> uint {
+        self.lines.iter().map(|l| l.char_len()).max().unwrap_or(0)
+    }
+    
+    /// Returns the number of lines below the baseline.
+    fn descent(&self) -> uint {
+        self.lines.len() - self.baseline - 1
+    }
+    
+    /// Places blocks side by side, aligning their baselines.
+    fn beside(blocks: &[Block]) -> Block {
+        let ascent = blocks.iter().map(|b| b.baseline).max().unwrap_or(0);
+        let descent = blocks.iter().map(|b| b.descent()).max().unwrap_or(0);
+        let mut lines = vec::from_elem(ascent + descent + 1, ~"");
+        
+        for b in blocks.iter() {
+            let width = b.width();
+            let top = ascent - b.baseline;
+            for (i, line) in lines.mut_iter().enumerate() {
+                let part = if i >= top && i < top + b.lines.len() { b.lines[i - top].as_slice() } else { "" };
+                line.push_str(pad(part, width));
+            }
+        }
+        
+        Block { lines: lines, baseline: ascent }
+    }
+    
+    /// Stacks blocks on top of each other, with the baseline in the middle.
+    fn above(blocks: &[Block]) -> Block {
+        let width = blocks.iter().map(|b| b.width()).max().unwrap_or(0);
+        let mut lines = ~[];
+        for b in blocks.iter() {
+            for l in b.lines.iter() {
+                lines.push(pad(l.as_slice(), width));
+            }
+        }
+        let baseline = (lines.len() - 1) / 2;
+        Block { lines: lines, baseline: baseline }
+    }
+    
+    /// Stacks a numerator over a denominator.
+    fn fraction(num: Block, den: Block) -> Block {
+        let width = cmp::max(num.width(), den.width()) + 2;
+        let mut lines = ~[];
+        for l in num.lines.iter() {
+            lines.push(center(l.as_slice(), width));
+        }
+        lines.push("─".repeat(width));
+        for l in den.lines.iter() {
+            lines.push(center(l.as_slice(), width));
+        }
+        Block { lines: lines, baseline: num.lines.len() }
+    }
+    
+    /// Raises a block to a power, using superscript characters when possible.
+    fn power(base: Block, exponent: Block) -> Block {
+        if exponent.lines.len() == 1 {
+            match superscript(exponent.lines[0].as_slice()) {
+                Some(s) => return Block::beside([base, Block::text(s.as_slice())]),
+                None    => ()
+            }
+        }
+        
+        // Put the exponent above the top right corner of the base.
+        let base_width = base.width();
+        let exponent_width = exponent.width();
+        let mut lines = ~[];
+        for l in exponent.lines.iter() {
+            lines.push(format!("{}{}", " ".repeat(base_width), pad(l.as_slice(), exponent_width)));
+        }
+        for l in base.lines.iter() {
+            lines.push(pad(l.as_slice(), base_width + exponent_width));
+        }
+        Block { lines: lines, baseline: exponent.lines.len() + base.baseline }
+    }
+    
+    /// Surrounds a block with brackets as tall as the block.
+    fn brackets(inner: Block) -> Block {
+        let height = inner.lines.len();
+        let baseline = inner.baseline;
+        Block::beside([
+            Block::column(height, baseline, "(", "⎛", "⎜", "⎝"),
+            inner,
+            Block::column(height, baseline, ")", "⎞", "⎟", "⎠")
+        ])
+    }
+    
+    /// A vertical bar of the given height.
+    fn bar(height: uint, baseline: uint) -> Block {
+        Block::column(height, baseline, "|", "│", "│", "│")
+    }
+    
+    /// A left curly brace of the given height.
+    fn brace(height: uint, baseline: uint) -> Block {
+        if height == 1 {
+            return Block::text("{")
+        }
+        
+        let mut lines = ~[];
+        for i in range(0, height) {
+            lines.push(if i == 0 {
+                ~"⎧"
+            } else if i == height - 1 {
+                ~"⎩"
+            } else if i == baseline {
+                ~"⎨"
+            } else {
+                ~"⎪"
+            });
+        }
+        Block { lines: lines, baseline: baseline }
+    }
+    
+    /// A column of characters of the given height, with distinct top, middle and bottom pieces.
+    fn column(height: uint, baseline: uint, single: &str, top: &str, middle: &str, bottom: &str) -> Block {
+        if height == 1 {
+            return Block::text(single)
+        }
+        
+        let mut lines = ~[];
+        for i in range(0, height) {
+            lines.push(if i == 0 {
+                top.to_owned()
+            } else if i == height - 1 {
+                bottom.to_owned()
+            } else {
+                middle.to_owned()
+            });
+        }
+        Block { lines: lines, baseline: baseline }
+    }
+}
@@ -0,0 +1,375 @@
+//! Canonical forms of sums and products.
+//! Sums and products are flattened, like terms and factors are collected and sorted,
+//! so equal functions are written the same way.
+
+use std::cmp::{Ordering, Less, Greater};
+
+use func::{DiffFunc, Constant, Power, Abs};
+use dag::{Graph, NodeId, Leaf, Plus, Minus, Mul, Div, Pow, LogOf, RootOf, Compose, Piecewise};
+use number::Number;
+
+/// Converts a function to canonical form.
+pub fn canonicalize(f: &DiffFunc) -> DiffFunc {
+    let mut graph = Graph::new();
+    let f = graph.insert(f);
+    let f = graph.canonicalize(f);
+    graph.extract(f)
+}
+
+/// Converts a node of a graph to canonical form.
+/// Operands are converted through the graph, which remembers the nodes it has converted.
+pub fn canonicalize_node(graph: &mut Graph, id: NodeId) -> NodeId {
+    let node = match graph.node(id) {
+        Plus(_, _) | Minus(_, _) => {
+            let mut sum = Sum { constant: Number::int(0), terms: ~[] };
+            sum.add(graph, id, &Number::int(1));
+            return sum.build(graph)
+        },
+        Mul(_, _) | Div(_, _) => {
+            let (c, fs) = factors(graph, id);
+            return product(graph, c, fs)
+        },
+        _ if power_of(graph, id).is_some() => {
+            let (c, fs) = factors(graph, id);
+            return product(graph, c, fs)
+        },
+        Pow(b, e) => Pow(graph.canonicalize(b), graph.canonicalize(e)),
+        LogOf(b, a) => LogOf(graph.canonicalize(b), graph.canonicalize(a)),
+        RootOf(n, a) => RootOf(graph.canonicalize(n), graph.canonicalize(a)),
+        Compose(o, i) => Compose(graph.canonicalize(o), graph.canonicalize(i)),
+        Piecewise(l, relation, r, t, o) => Piecewise(
+            graph.canonicalize(l),
+            relation,
+            graph.canonicalize(r),
+            graph.canonicalize(t),
+            graph.canonicalize(o)
+        ),
+        Leaf(_) => return id
+    };
+    graph.intern(node)
+}
+
+/// Returns the coefficient of a product and its factors with their exponents.
+pub fn factors(graph: &mut Graph, id: NodeId) -> (Number, ~[(NodeId, Number)]) {
+    let mut product = Product { coefficient: Number::int(1), factors: ~[] };
+    product.mul(graph, id, &Number::int(1));
+    (product.coefficient, product.factors)
+}
+
+/// Builds a product from a coefficient and factors with their exponents.
+pub fn product(graph: &mut Graph, coefficient: Number, factors: ~[(NodeId, Number)]) -> NodeId {
+    let (c, term) = Product { coefficient: coefficient, factors: factors }.split(graph);
+    scale(graph, term, c)
+}
+
+/// Builds (g^a)^b as a single power of g.
+/// Even powers are never negative, so their non-integer powers are powers of |g|.
+pub fn power_of_power(graph: &mut Graph, g: NodeId, a: &Number, b: &Number) -> NodeId {
+    let base = if is_even(a) && !b.is_integer() { abs(graph, g) } else { g };
+    let power = graph.intern(Leaf(Power(*a * *b)));
+    graph.compose(power, base)
+}
+
+/// A constant plus terms with coefficients.
+struct Sum {
+    constant: Number,
+    terms: ~[(NodeId, Number)]
+}
+
+/// A coefficient times factors with exponents.
+struct Product {
+    coefficient: Number,
+    factors: ~[(NodeId, Number)]
+}
+
+impl Sum {
+    /// Adds a node multiplied by a factor.
+    fn add(&mut self, graph: &mut Graph, id: NodeId, factor: &Number) {
+        match graph.node(id) {
+            Plus(l, r) => {
+                self.add(graph, l, factor);
+                self.add(graph, r, factor);
+            },
+            Minus(l, r) => {
+                self.add(graph, l, factor);
+                self.add(graph, r, &-*factor);
+            },
+            Leaf(Constant(ref c)) => self.constant = self.constant + *factor * *c,
+            _ => {
+                let mut product = Product { coefficient: Number::int(1), factors: ~[] };
+                product.mul(graph, id, &Number::int(1));
+                let (c, term) = product.split(graph);
+                if graph.is_one(term) {
+                    self.constant = self.constant + *factor * c;
+                } else {
+                    self.add_term(term, *factor * c);
+                }
+            }
+        }
+    }
+    
+    /// Adds a term, collecting it with an equal one.
+    fn add_term(&mut self, term: NodeId, coefficient: Number) {
+        match self.terms.iter().position(|&(t, _)| t == term) {
+            Some(i) => match self.terms[i] { (_, ref mut c) => *c = *c + coefficient },
+            None => self.terms.push((term, coefficient))
+        }
+    }
+    
+    /// Builds the sum, with the terms sorted and the constant last.
+    fn build(self, graph: &mut Graph) -> NodeId {
+        let Sum { constant, terms } = self;
+        let mut terms: ~[(NodeId, Number)] = terms.move_iter().filter(|&(_, ref c)| !c.is_zero()).collect();
+        terms.sort_by(|&(a, _), &(b, _)| compare(graph, a, b));
+        
+        let mut sum = None;
+        for (term, c) in terms.move_iter() {
+            sum = Some(match sum {
+                None => scale(graph, term, c),
+                Some(s) => if c.is_negative() {
+                    let term = scale(graph, term, -c);
+                    graph.intern(Minus(s, term))
+                } else {
+                    let term = scale(graph, term, c);
+                    graph.intern(Plus(s, term))
+                }
+            });
+        }
+        
+        match sum {
+            None => number(graph, constant),
+            Some(s) => if constant.is_negative() {
+                let c = number(graph, -constant);
+                graph.intern(Minus(s, c))
+            } else if !constant.is_zero() {
+                let c = number(graph, constant);
+                graph.intern(Plus(s, c))
+            } else {
+                s
+            }
+        }
+    }
+}
+
+impl Product {
+    /// Multiplies by a node raised to an exponent.
+    fn mul(&mut self, graph: &mut Graph, id: NodeId, exponent: &Number) {
+        match graph.node(id) {
+            Mul(l, r) => {
+                self.mul(graph, l, exponent);
+                self.mul(graph, r, exponent);
+            },
+            Div(l, r) => {
+                self.mul(graph, l, exponent);
+                self.mul(graph, r, &-*exponent);
+            },
+            Leaf(Constant(ref c)) => self.coefficient = self.coefficient * c.pow(exponent),
+            Leaf(Power(ref p)) => {
+                let x = graph.intern(Leaf(Power(Number::int(1))));
+                self.add_power(graph, x, p, exponent)
+            },
+            _ => match power_of(graph, id) {
+                // An even power with a non-integer exponent is a power of |g|, which is not split.
+                Some((ref p, g)) if is_even(p) && !exponent.is_integer() => {
+                    let g = graph.canonicalize(g);
+                    self.add_power(graph, g, p, exponent)
+                },
+                
+                // Only integer powers of products are split into powers of their factors.
+                Some((p, g)) => match graph.node(g) {
+                    Mul(_, _) | Div(_, _) if !p.is_integer() => {
+                        let g = graph.canonicalize(g);
+                        self.add_factor(g, p * *exponent)
+                    },
+                    _ => self.mul(graph, g, &(p * *exponent))
+                },
+                
+                None => {
+                    // Sums can collapse into products.
+                    let g = graph.canonicalize(id);
+                    match graph.node(g) {
+                        Mul(_, _) | Div(_, _) | Leaf(Constant(_)) | Leaf(Power(_)) => self.mul(graph, g, exponent),
+                        _ if power_of(graph, g).is_some() => self.mul(graph, g, exponent),
+                        _ => self.add_factor(g, exponent)
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Multiplies by (g^p)^exponent, like power_of_power.
+    fn add_power(&mut self, graph: &mut Graph, g: NodeId, p: &Number, exponent: &Number) {
+        let base = if is_even(p) && !exponent.is_integer() { abs(graph, g) } else { g };
+        self.add_factor(base, *p * *exponent)
+    }
+    
+    /// Multiplies by a factor, collecting it with an equal one.
+    fn add_factor(&mut self, base: NodeId, exponent: Number) {
+        match self.factors.iter().position(|&(b, _)| b == base) {
+            Some(i) => match self.factors[i] { (_, ref mut e) => *e = *e + exponent },
+            None => self.factors.push((base, exponent))
+        }
+    }
+    
+    /// Splits the product into its coefficient and the product of the factors.
+    /// Factors with negative exponents go to the denominator.
+    fn split(self, graph: &mut Graph) -> (Number, NodeId) {
+        let Product { coefficient, factors } = self;
+        if coefficient.is_zero() {
+            return (coefficient, number(graph, Number::int(1)))
+        }
+        
+        let mut factors: ~[(NodeId, Number)] = factors.move_iter().filter(|&(_, ref e)| !e.is_zero()).collect();
+        factors.sort_by(|&(a, _), &(b, _)| compare(graph, a, b));
+        
+        let mut numerator = None;
+        let mut denominator = None;
+        for (base, e) in factors.move_iter() {
+            if !e.is_negative() {
+                let f = power(graph, base, e);
+                numerator = Some(times(graph, numerator, f));
+            } else {
+                let f = power(graph, base, -e);
+                denominator = Some(times(graph, denominator, f));
+            }
+        }
+        
+        let numerator = match numerator {
+            Some(n) => n,
+            None => number(graph, Number::int(1))
+        };
+        match denominator {
+            None => (coefficient, numerator),
+            Some(d) => (coefficient, graph.intern(Div(numerator, d)))
+        }
+    }
+}
+
+/// Multiplies a node by a coefficient.
+fn scale(graph: &mut Graph, f: NodeId, c: Number) -> NodeId {
+    match graph.node(f) {
+        _ if c.is_one() => f,
+        Leaf(Constant(x)) => number(graph, c * x),
+        Div(l, r) => {
+            let l = scale(graph, l, c);
+            graph.intern(Div(l, r))
+        },
+        _ => {
+            let c = number(graph, c);
+            graph.intern(Mul(c, f))
+        }
+    }
+}
+
+/// Multiplies an optional product by a factor.
+fn times(graph: &mut Graph, product: Option<NodeId>, f: NodeId) -> NodeId {
+    match product {
+        None => f,
+        Some(p) => graph.intern(Mul(p, f))
+    }
+}
+
+/// Raises a node to a power.
+fn power(graph: &mut Graph, base: NodeId, e: Number) -> NodeId {
+    match graph.node(base) {
+        _ if e.is_one() => base,
+        Leaf(Power(p)) => graph.intern(Leaf(Power(p * e))),
+        _ => {
+            let power = graph.intern(Leaf(Power(e)));
+            graph.intern(Compose(power, base))
+        }
+    }
+}
+
+/// Returns |g|, which is g itself if it is already an absolute value.
+fn abs(graph: &mut Graph, g: NodeId) -> NodeId {
+    match graph.node(g) {
+        Leaf(Abs) => g,
+        Compose(o, _) if graph.node(o) == Leaf(Abs) => g,
+        _ => {
+            let abs = graph.intern(Leaf(Abs));
+            graph.compose(abs, g)
+        }
+    }
+}
+
+/// Adds a constant.
+fn number(graph: &mut Graph, c: Number) -> NodeId {
+    graph.intern(Leaf(Constant(c)))
+}
+
+/// Returns p and g for the node g^p.
+fn power_of(graph: &Graph, id: NodeId) -> Option<(Number, NodeId)> {
+    match graph.node(id) {
+        Compose(o, g) => match graph.node(o) {
+            Leaf(Power(p)) => Some((p, g)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Checks whether a number is an even integer.
+fn is_even(a: &Number) -> bool {
+    (*a / Number::int(2)).is_integer()
+}
+
+/// Returns the degree of a term in x.
+fn degree(graph: &Graph, id: NodeId) -> f64 {
+    match graph.node(id) {
+        Leaf(Power(p)) => p.to_f64(),
+        Mul(l, r) => degree(graph, l) + degree(graph, r),
+        Div(l, r) => degree(graph, l) - degree(graph, r),
+        _ => match power_of(graph, id) {
+            Some((p, g)) => p.to_f64() * degree(graph, g),
+            None => 0.0
+        }
+    }
+}
+
+/// Orders terms by descending degree, then by their text.
+fn compare(graph: &Graph, a: NodeId, b: NodeId) -> Ordering {
+    let (da, db) = (degree(graph, a), degree(graph, b));
+    if da > db {
+        Less
+    } else if da < db {
+        Greater
+    } else {
+        graph.extract(a).to_str("x").cmp(&graph.extract(b).to_str("x"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::canonicalize;
+    use func::{Constant, Power, Abs, Plus, Mul, Compose, Root};
+    use simplify::Simplify;
+    use number::Number;
+    
+    #[test]
+    fn like_terms_and_factors_are_collected() {
+        // x + x = 2x, x * 2 * x = 2x^2
+        assert_eq!(canonicalize(&Plus { left: ~Power(Number::int(1)), right: ~Power(Number::int(1)) }),
+                   canonicalize(&Mul { left: ~Constant(Number::int(2)), right: ~Power(Number::int(1)) }));
+        assert_eq!(
+            canonicalize(&Mul { left: ~Mul { left: ~Power(Number::int(1)), right: ~Constant(Number::int(2)) }, right: ~Power(Number::int(1)) }),
+            canonicalize(&Mul { left: ~Constant(Number::int(2)), right: ~Power(Number::int(2)) })
+        );
+    }
+    
+    #[test]
+    fn odd_powers_of_powers_are_merged() {
+        // (x^3)^(1/3) = x
+        let f = Compose { outer: ~Power(Number::ratio(1, 3)), inner: ~Power(Number::int(3)) };
+        assert_eq!(canonicalize(&f), Power(Number::int(1)));
+    }
+    
+    #[test]
+    fn roots_of_even_powers_are_absolute_values() {
+        // (x^2)^(1/2) = sqrt(x^2) = |x|
+        let f = Compose { outer: ~Power(Number::ratio(1, 2)), inner: ~Power(Number::int(2)) };
+        assert_eq!(canonicalize(&f), Abs);
+        let f = Compose { outer: ~Root(Number::int(2)), inner: ~Power(Number::int(2)) };
+        assert_eq!(f.simplify(), Abs);
+    }
+}
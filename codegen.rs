@@ -0,0 +1,383 @@
+//! Rust and C code generation.
+//! Subexpressions used more than once are computed once and bound to local variables.
+
+use std::hashmap::HashMap;
+
+use func::DiffFunc;
+use pretty;
+use pretty::{Expr, Num, Sym, Neg, Add, Sub, Mul, Div, Pow, Call, Cases, ADD, MUL, NEG, ATOM};
+
+/// A target language.
+#[deriving(Eq)]
+pub enum Language {
+    Rust,
+    C
+}
+
+static HALF_PI: f64 = 1.5707963267948966;
+
+/// Generates a function 'f' of 'x' and the free variables.
+pub fn generate(f: &DiffFunc, language: Language) -> Result<~str, ~str> {
+    let e = pretty::lower(f, &Sym(~"x"));
+    
+    let mut params = ~[~"x"];
+    collect_params(&e, &mut params);
+    
+    let mut gen = Generator {
+        language: language,
+        counts: HashMap::new(),
+        names: HashMap::new(),
+        bindings: ~[],
+        error: None
+    };
+    gen.count(&e);
+    let (result, _) = gen.emit(&e);
+    match gen.error {
+        Some(error) => return Err(error),
+        None => ()
+    }
+    
+    let mut code = ~"";
+    match language {
+        Rust => {
+            let params: ~[~str] = params.iter().map(|p| format!("{}: f64", *p)).collect();
+            code.push_str(format!("fn f({}) -> f64 \\{\n", params.connect(", ")));
+            for &(ref name, ref value) in gen.bindings.iter() {
+                code.push_str(format!("    let {}: f64 = {};\n", *name, *value));
+            }
+            code.push_str(format!("    {}\n\\}", result));
+        },
+        C => {
+            let params: ~[~str] = params.iter().map(|p| format!("double {}", *p)).collect();
+            code.push_str(format!("\\#include <math.h>\n\ndouble f({}) \\{\n", params.connect(", ")));
+            for &(ref name, ref value) in gen.bindings.iter() {
+                code.push_str(format!("    const double {} = {};\n", *name, *value));
+            }
+            code.push_str(format!("    return {};\n\\}", result));
+        }
+    }
+    Ok(code)
+}
+
+/// Collects the free variables of an expression.
+fn collect_params(e: &Expr, params: &mut ~[~str]) {
+    match *e {
+        Sym(ref s) => if !is_undefined(e) && constant(s.as_slice()).is_none() && !params.contains(s) {
+            params.push(s.clone());
+        },
+        _ => for child in children(e).iter() {
+            collect_params(*child, params);
+        }
+    }
+}
+
+/// Returns the operands of an expression.
+fn children<'a>(e: &'a Expr) -> ~[&'a Expr] {
+    match *e {
+        Num(_) | Sym(_) => ~[],
+        Neg(ref a) => ~[&**a],
+        Add(ref a, ref b) | Sub(ref a, ref b) | Mul(ref a, ref b) | Div(ref a, ref b) | Pow(ref a, ref b) =>
+            ~[&**a, &**b],
+        Call(_, ref args) => args.iter().collect(),
+        Cases(ref cases, ref default) => {
+            let mut result = ~[];
+            for c in cases.iter() {
+                result.push(&c.left);
+                result.push(&c.right);
+                result.push(&c.value);
+            }
+            for d in default.iter() {
+                result.push(&**d);
+            }
+            result
+        }
+    }
+}
+
+/// Checks whether the expression is the undefined value.
+fn is_undefined(e: &Expr) -> bool {
+    match *e {
+        Sym(ref s) => s.as_slice() == "undefined",
+        _ => false
+    }
+}
+
+/// Returns the Rust and C names of a symbolic constant.
+fn constant(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "pi" => Some(("std::f64::consts::pi", "M_PI")),
+        "e"  => Some(("std::f64::consts::e", "M_E")),
+        _ => None
+    }
+}
+
+/// Emits code, sharing common subexpressions.
+struct Generator {
+    language: Language,
+    
+    /// How many times each subexpression is used, by its text.
+    counts: HashMap<~str, uint>,
+    
+    /// Variables bound to subexpressions, by their text.
+    names: HashMap<~str, ~str>,
+    
+    /// Variable bindings in evaluation order.
+    bindings: ~[(~str, ~str)],
+    
+    /// The first expression that could not be generated.
+    error: Option<~str>
+}
+
+impl Generator {
+    /// Counts the uses of every subexpression.
+    /// Operands of repeated subexpressions are counted only once.
+    fn count(&mut self, e: &Expr) {
+        match *e {
+            Num(_) | Sym(_) => return,
+            _ => ()
+        }
+        
+        let first = {
+            let count = self.counts.find_or_insert(e.to_text(), 0);
+            *count += 1;
+            *count == 1
+        };
+        
+        if first {
+            for child in children(e).iter() {
+                self.count(*child);
+            }
+        }
+    }
+    
+    /// Emits an expression, returning the code and its precedence.
+    fn emit(&mut self, e: &Expr) -> (~str, uint) {
+        let key = e.to_text();
+        match self.names.find(&key) {
+            Some(name) => return (name.clone(), ATOM),
+            None => ()
+        }
+        
+        let (code, precedence) = self.emit_node(e);
+        if self.counts.find(&key).map_or(false, |c| *c > 1) {
+            self.bind(code, Some(key))
+        } else {
+            (code, precedence)
+        }
+    }
+    
+    /// Binds code to a new variable.
+    fn bind(&mut self, code: ~str, key: Option<~str>) -> (~str, uint) {
+        let name = format!("t{}", self.bindings.len());
+        self.bindings.push((name.clone(), code));
+        match key {
+            Some(key) => { self.names.insert(key, name.clone()); },
+            None => ()
+        }
+        (name, ATOM)
+    }
+    
+    /// Emits an expression as an atom, binding it to a variable if needed.
+    fn emit_atom(&mut self, e: &Expr) -> ~str {
+        let (code, precedence) = self.emit(e);
+        if precedence == ATOM {
+            code
+        } else {
+            let (name, _) = self.bind(code, None);
+            name
+        }
+    }
+    
+    /// Emits the receiver of a Rust method call.
+    fn emit_receiver(&mut self, e: &Expr) -> ~str {
+        let (code, precedence) = self.emit(e);
+        if precedence == ATOM { code } else { format!("({})", code) }
+    }
+    
+    /// Emits an operand, with brackets if needed.
+    fn emit_operand(&mut self, parent: &Expr, e: &Expr, right: bool) -> ~str {
+        let (code, precedence) = self.emit(e);
+        if parent.needs_brackets_for(precedence, right) {
+            format!("({})", code)
+        } else {
+            code
+        }
+    }
+    
+    /// Emits a binary operator.
+    fn emit_binary(&mut self, e: &Expr, left: &Expr, op: &str, right: &Expr, precedence: uint) -> (~str, uint) {
+        let left = self.emit_operand(e, left, false);
+        let right = self.emit_operand(e, right, true);
+        (format!("{} {} {}", left, op, right), precedence)
+    }
+    
+    /// Emits an expression without sharing it.
+    fn emit_node(&mut self, e: &Expr) -> (~str, uint) {
+        match *e {
+            Num(ref n) => (self.number(n.to_f64()), ATOM),
+            Sym(ref s) => if is_undefined(e) {
+                (self.nan(), ATOM)
+            } else {
+                match (constant(s.as_slice()), self.language) {
+                    (Some((rust, _)), Rust) => (rust.to_owned(), ATOM),
+                    (Some((_, c)), C) => (c.to_owned(), ATOM),
+                    (None, _) => (s.clone(), ATOM)
+                }
+            },
+            Neg(ref a) => (format!("-{}", self.emit_operand(e, &**a, false)), NEG),
+            Add(ref a, ref b) => self.emit_binary(e, &**a, "+", &**b, ADD),
+            Sub(ref a, ref b) => self.emit_binary(e, &**a, "-", &**b, ADD),
+            Mul(ref a, ref b) => self.emit_binary(e, &**a, "*", &**b, MUL),
+            Div(ref a, ref b) => self.emit_binary(e, &**a, "/", &**b, MUL),
+            
+            Pow(ref a, ref b) => {
+                match self.language {
+                    Rust => {
+                        let base = self.emit_receiver(&**a);
+                        match **b {
+                            Num(ref n) if n.is_integer() && n.to_f64().abs() < 1e9 =>
+                                (format!("{}.powi({})", base, n.to_f64() as i32), ATOM),
+                            _ => {
+                                let exponent = self.emit_atom(&**b);
+                                (format!("{}.powf(&{})", base, exponent), ATOM)
+                            }
+                        }
+                    },
+                    C => {
+                        let (base, _) = self.emit(&**a);
+                        let (exponent, _) = self.emit(&**b);
+                        (format!("pow({}, {})", base, exponent), ATOM)
+                    }
+                }
+            },
+            
+            Call(ref name, ref args) => self.emit_call(name.as_slice(), args.as_slice()),
+            
+            Cases(ref cases, ref default) => {
+                let otherwise = match *default {
+                    Some(ref d) => { let (code, _) = self.emit(&**d); code },
+                    None => self.nan()
+                };
+                
+                // Build the conditional from the last case.
+                let mut code = otherwise;
+                for c in cases.rev_iter() {
+                    let (left, _) = self.emit(&c.left);
+                    let (right, _) = self.emit(&c.right);
+                    let (value, _) = self.emit(&c.value);
+                    let cond = format!("{} {} {}", left, c.relation.symbol(), right);
+                    code = match self.language {
+                        Rust => format!("if {} \\{ {} \\} else \\{ {} \\}", cond, value, code),
+                        C    => format!("{} ? {} : {}", cond, value, code)
+                    };
+                }
+                (format!("({})", code), ATOM)
+            }
+        }
+    }
+    
+    /// Emits a function call.
+    fn emit_call(&mut self, name: &str, args: &[Expr]) -> (~str, uint) {
+        let arg = &args[args.len() - 1];
+        
+        match self.language {
+            Rust => match name {
+                "cot" => (format!("1.0 / {}.tan()", self.emit_receiver(arg)), MUL),
+                "acot" => (format!("{} - {}.atan()", self.number(HALF_PI), self.emit_receiver(arg)), ADD),
+                "sign" => {
+                    let a = self.emit_atom(arg);
+                    (format!("(if {} > 0.0 \\{ 1.0 \\} else if {} < 0.0 \\{ -1.0 \\} else \\{ {} \\})", a, a, a), ATOM)
+                },
+                "log" => {
+                    let base = self.emit_atom(&args[0]);
+                    (format!("{}.log(&{})", self.emit_receiver(arg), base), ATOM)
+                },
+                "root" => match args[0] {
+                    Num(ref n) if n.to_f64() == 3.0 => (format!("{}.cbrt()", self.emit_receiver(arg)), ATOM),
+                    Num(ref n) => (format!("{}.powf(&{})", self.emit_receiver(arg), self.number(1.0 / n.to_f64())), ATOM),
+                    _ => self.unsupported("a root of a non-constant degree")
+                },
+                _ => (format!("{}.{}()", self.emit_receiver(arg), name), ATOM)
+            },
+            
+            // The argument is used three times, so it is bound to a variable.
+            C if name == "sign" => {
+                let a = self.emit_atom(arg);
+                (format!("({} > 0.0 ? 1.0 : {} < 0.0 ? -1.0 : {})", a, a, a), ATOM)
+            },
+            
+            C => {
+                let (a, _) = self.emit(arg);
+                match name {
+                    "cot" => (format!("1.0 / tan({})", a), MUL),
+                    "acot" => (format!("{} - atan({})", self.number(HALF_PI), a), ADD),
+                    "log" => {
+                        let (base, _) = self.emit(&args[0]);
+                        (format!("log({}) / log({})", a, base), MUL)
+                    },
+                    "root" => match args[0] {
+                        Num(ref n) if n.to_f64() == 3.0 => (format!("cbrt({})", a), ATOM),
+                        Num(ref n) => (format!("pow({}, {})", a, self.number(1.0 / n.to_f64())), ATOM),
+                        _ => self.unsupported("a root of a non-constant degree")
+                    },
+                    "ln" => (format!("log({})", a), ATOM),
+                    "abs" => (format!("fabs({})", a), ATOM),
+                    _ => (format!("{}({})", name, a), ATOM)
+                }
+            }
+        }
+    }
+    
+    /// Records an expression that cannot be generated, and emits not-a-number in its place.
+    fn unsupported(&mut self, what: &str) -> (~str, uint) {
+        if self.error.is_none() {
+            self.error = Some(format!("Cannot generate code for {}.", what));
+        }
+        (self.nan(), ATOM)
+    }
+    
+    /// Formats a floating point literal, typed in Rust.
+    fn number(&self, f: f64) -> ~str {
+        let literal = if f == f.floor() && f.abs() < 1e15 {
+            format!("{}.0", f as i64)
+        } else {
+            format!("{}", f)
+        };
+        match self.language {
+            Rust => format!("{}f64", literal),
+            C    => literal
+        }
+    }
+    
+    /// Returns the not-a-number constant.
+    fn nan(&self) -> ~str {
+        match self.language {
+            Rust => ~"Float::nan()",
+            C    => ~"NAN"
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate, Rust, C};
+    use func::{Sin, Constant, Power, Mul, Div, Pow, Compose};
+    use number::Number;
+    
+    #[test]
+    fn constant_bases_are_typed() {
+        let half = Pow { base: ~Div { left: ~Constant(Number::int(1)), right: ~Constant(Number::int(2)) }, exponent: ~Power(Number::int(1)) };
+        assert_eq!(generate(&half, Rust), Ok(~"fn f(x: f64) -> f64 {\n    (1.0f64 / 2.0f64).powf(&x)\n}"));
+        
+        let negative = Pow { base: ~Constant(Number::int(-2)), exponent: ~Power(Number::int(1)) };
+        assert_eq!(generate(&negative, Rust), Ok(~"fn f(x: f64) -> f64 {\n    (-2.0f64).powf(&x)\n}"));
+        assert_eq!(generate(&negative, C), Ok(~"#include <math.h>\n\ndouble f(double x) {\n    return pow(-2.0, x);\n}"));
+    }
+    
+    #[test]
+    fn repeated_subexpressions_are_bound() {
+        let sin = Compose { outer: ~Sin, inner: ~Power(Number::int(1)) };
+        let square = Mul { left: ~sin.clone(), right: ~sin };
+        assert_eq!(generate(&square, Rust), Ok(~"fn f(x: f64) -> f64 {\n    let t0: f64 = x.sin();\n    t0 * t0\n}"));
+    }
+}
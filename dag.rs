@@ -0,0 +1,561 @@
+//! Hash-consed function graphs.
+//! Equal subfunctions are stored once and referred to by index, so derivatives share
+//! their operands instead of copying them, and equal functions have equal indices.
+
+use std::hashmap::HashMap;
+
+use func;
+use func::{DiffFunc, Relation, Condition};
+use number::Number;
+use simplify::rewrite_node;
+use canon::canonicalize_node;
+use rational::normalize_node;
+
+/// The index of a node in a graph.
+pub type NodeId = uint;
+
+/// The most times a node is rewritten after its operands are simplified.
+static MAX_REWRITES: uint = 100;
+
+/// A node of a graph.
+/// Functions without operands are stored as leaves.
+#[deriving(Clone, Eq, IterBytes)]
+pub enum Node {
+    Leaf(DiffFunc),
+    Plus(NodeId, NodeId),
+    Minus(NodeId, NodeId),
+    Mul(NodeId, NodeId),
+    Div(NodeId, NodeId),
+    Pow(NodeId, NodeId),
+    LogOf(NodeId, NodeId),
+    RootOf(NodeId, NodeId),
+    Compose(NodeId, NodeId),
+    Piecewise(NodeId, Relation, NodeId, NodeId, NodeId)
+}
+
+/// A graph of functions with shared subfunctions.
+pub struct Graph {
+    priv nodes: ~[Node],
+    priv index: HashMap<Node, NodeId>,
+    priv partials: HashMap<(NodeId, ~str), NodeId>,
+    priv simplified: HashMap<NodeId, NodeId>,
+    priv canonical: HashMap<NodeId, NodeId>,
+    priv normalized: HashMap<NodeId, NodeId>
+}
+
+impl Graph {
+    /// Constructs an empty graph.
+    pub fn new() -> Graph {
+        Graph {
+            nodes: ~[],
+            index: HashMap::new(),
+            partials: HashMap::new(),
+            simplified: HashMap::new(),
+            canonical: HashMap::new(),
+            normalized: HashMap::new()
+        }
+    }
+    
+    /// Adds a function, returning the index of its root node.
+    pub fn insert(&mut self, f: &DiffFunc) -> NodeId {
+        let node = match *f {
+            func::Plus { left: ref l, right: ref r } => Plus(self.insert(&**l), self.insert(&**r)),
+            func::Minus { left: ref l, right: ref r } => Minus(self.insert(&**l), self.insert(&**r)),
+            func::Mul { left: ref l, right: ref r } => Mul(self.insert(&**l), self.insert(&**r)),
+            func::Div { left: ref l, right: ref r } => Div(self.insert(&**l), self.insert(&**r)),
+            func::Pow { base: ref b, exponent: ref e } => Pow(self.insert(&**b), self.insert(&**e)),
+            func::LogOf { base: ref b, arg: ref a } => LogOf(self.insert(&**b), self.insert(&**a)),
+            func::RootOf { degree: ref n, arg: ref a } => RootOf(self.insert(&**n), self.insert(&**a)),
+            func::Compose { outer: ref o, inner: ref i } => Compose(self.insert(&**o), self.insert(&**i)),
+            func::Piecewise { cond: ref c, then: ref t, otherwise: ref o } => Piecewise(
+                self.insert(&*c.left),
+                c.relation,
+                self.insert(&*c.right),
+                self.insert(&**t),
+                self.insert(&**o)
+            ),
+            ref leaf => Leaf(leaf.clone())
+        };
+        self.intern(node)
+    }
+    
+    /// Converts a node back to a function.
+    /// Shared nodes are copied into every place they are used.
+    pub fn extract(&self, id: NodeId) -> DiffFunc {
+        match self.nodes[id] {
+            Leaf(ref f) => f.clone(),
+            Plus(l, r) => func::Plus { left: ~self.extract(l), right: ~self.extract(r) },
+            Minus(l, r) => func::Minus { left: ~self.extract(l), right: ~self.extract(r) },
+            Mul(l, r) => func::Mul { left: ~self.extract(l), right: ~self.extract(r) },
+            Div(l, r) => func::Div { left: ~self.extract(l), right: ~self.extract(r) },
+            Pow(b, e) => func::Pow { base: ~self.extract(b), exponent: ~self.extract(e) },
+            LogOf(b, a) => func::LogOf { base: ~self.extract(b), arg: ~self.extract(a) },
+            RootOf(n, a) => func::RootOf { degree: ~self.extract(n), arg: ~self.extract(a) },
+            Compose(o, i) => func::Compose { outer: ~self.extract(o), inner: ~self.extract(i) },
+            Piecewise(l, relation, r, t, o) => func::Piecewise {
+                cond: Condition { left: ~self.extract(l), relation: relation, right: ~self.extract(r) },
+                then: ~self.extract(t),
+                otherwise: ~self.extract(o)
+            }
+        }
+    }
+    
+    /// Computes the partial derivative of a node with respect to a variable.
+    /// Every node is differentiated at most once per variable.
+    pub fn partial(&mut self, id: NodeId, var: &str) -> NodeId {
+        let key = (id, var.to_owned());
+        match self.partials.find(&key) {
+            Some(&d) => return d,
+            None => ()
+        }
+        
+        let d = match self.nodes[id].clone() {
+            Leaf(f) => self.insert(&f.partial(var)),
+            Plus(l, r) => {
+                let (dl, dr) = (self.partial(l, var), self.partial(r, var));
+                self.plus(dl, dr)
+            },
+            Minus(l, r) => {
+                let (dl, dr) = (self.partial(l, var), self.partial(r, var));
+                self.minus(dl, dr)
+            },
+            Mul(l, r) => {
+                let (dl, dr) = (self.partial(l, var), self.partial(r, var));
+                let left = self.mul(dl, r);
+                let right = self.mul(l, dr);
+                self.plus(left, right)
+            },
+            Div(l, r) => {
+                let (dl, dr) = (self.partial(l, var), self.partial(r, var));
+                let left = self.mul(dl, r);
+                let right = self.mul(l, dr);
+                let numerator = self.minus(left, right);
+                let square = self.intern(Leaf(func::Power(Number::int(2))));
+                let denominator = self.compose(square, r);
+                self.div(numerator, denominator)
+            },
+            Pow(b, e) => self.power_rule(id, b, e, var),
+            LogOf(b, a) => {
+                // log(b, f) = ln(f) / ln(b), like func::log_quotient.
+                let ln = self.intern(Leaf(func::Ln));
+                let (ln_arg, ln_base) = (self.compose(ln, a), self.compose(ln, b));
+                let quotient = self.intern(Div(ln_arg, ln_base));
+                self.partial(quotient, var)
+            },
+            RootOf(n, a) => {
+                // root(n, f) = f^(1 / n), like func::root_power.
+                let one = self.intern(Leaf(func::Constant(Number::int(1))));
+                let exponent = self.intern(Div(one, n));
+                let power = self.intern(Pow(a, exponent));
+                self.partial(power, var)
+            },
+            Compose(o, i) => {
+                let d_outer = self.partial(o, "x");
+                let left = self.compose(d_outer, i);
+                let right = self.partial(i, var);
+                let chain = self.mul(left, right);
+                
+                // The outer function can use the variable too, like f(t) = t y.
+                let d_var = self.partial(o, var);
+                if var == "x" || self.is_zero(d_var) {
+                    chain
+                } else {
+                    let held = self.compose(d_var, i);
+                    self.plus(chain, held)
+                }
+            },
+            Piecewise(l, relation, r, t, o) => {
+                // Undefined where both sides of the condition are equal, like func::piecewise_derivative.
+                let (dt, d_otherwise) = (self.partial(t, var), self.partial(o, var));
+                let (below, above) = match relation {
+                    func::Lt | func::Le => (dt, d_otherwise),
+                    func::Gt | func::Ge => (d_otherwise, dt)
+                };
+                let undefined = self.intern(Leaf(func::Undefined));
+                let equal = self.intern(Piecewise(l, func::Gt, r, above, undefined));
+                self.intern(Piecewise(l, func::Lt, r, below, equal))
+            }
+        };
+        
+        self.partials.insert(key, d);
+        d
+    }
+    
+    /// Simplifies a node.
+    /// Operands are simplified first, and every node is simplified at most once.
+    pub fn simplify(&mut self, id: NodeId) -> NodeId {
+        self.simplify_within(id, MAX_REWRITES)
+    }
+    
+    /// Simplifies a node, rewriting it at most a number of times in case the rules cycle.
+    fn simplify_within(&mut self, id: NodeId, rewrites: uint) -> NodeId {
+        match self.simplified.find(&id) {
+            Some(&s) => return s,
+            None => ()
+        }
+        
+        let node = match self.nodes[id].clone() {
+            Leaf(f) => Leaf(f),
+            Plus(l, r) => Plus(self.simplify(l), self.simplify(r)),
+            Minus(l, r) => Minus(self.simplify(l), self.simplify(r)),
+            Mul(l, r) => Mul(self.simplify(l), self.simplify(r)),
+            Div(l, r) => Div(self.simplify(l), self.simplify(r)),
+            Pow(b, e) => Pow(self.simplify(b), self.simplify(e)),
+            LogOf(b, a) => LogOf(self.simplify(b), self.simplify(a)),
+            RootOf(n, a) => RootOf(self.simplify(n), self.simplify(a)),
+            Compose(o, i) => Compose(self.simplify(o), self.simplify(i)),
+            Piecewise(l, relation, r, t, o) =>
+                Piecewise(self.simplify(l), relation, self.simplify(r), self.simplify(t), self.simplify(o))
+        };
+        let operands = self.intern(node);
+        
+        // Rewrite until nothing changes, which is a comparison of indices.
+        let next = rewrite_node(self, operands);
+        let result = if next == operands || rewrites == 0 { next } else { self.simplify_within(next, rewrites - 1) };
+        
+        self.simplified.insert(id, result);
+        self.simplified.insert(result, result);
+        result
+    }
+    
+    /// Brings a node into canonical form.
+    /// Every node is converted at most once.
+    pub fn canonicalize(&mut self, id: NodeId) -> NodeId {
+        match self.canonical.find(&id) {
+            Some(&c) => return c,
+            None => ()
+        }
+        
+        let result = canonicalize_node(self, id);
+        self.canonical.insert(id, result);
+        self.canonical.insert(result, result);
+        result
+    }
+    
+    /// Brings a node into the form numerator / denominator.
+    /// Every node is normalized at most once.
+    pub fn normalize(&mut self, id: NodeId) -> NodeId {
+        match self.normalized.find(&id) {
+            Some(&n) => return n,
+            None => ()
+        }
+        
+        let result = normalize_node(self, id);
+        self.normalized.insert(id, result);
+        self.normalized.insert(result, result);
+        result
+    }
+    
+    /// Evaluates a node at a point, like DiffFunc::eval.
+    /// Shared nodes are evaluated once.
+    pub fn eval(&self, id: NodeId, x: f64) -> f64 {
+        let mut values = HashMap::new();
+        self.eval_within(id, x, &mut values)
+    }
+    
+    /// Evaluates a node at a point, remembering the values of the nodes evaluated so far.
+    fn eval_within(&self, id: NodeId, x: f64, values: &mut HashMap<NodeId, f64>) -> f64 {
+        match values.find(&id) {
+            Some(&v) => return v,
+            None => ()
+        }
+        
+        let value = match self.nodes[id] {
+            Leaf(ref f) => f.eval(x),
+            Plus(l, r) => self.eval_within(l, x, values) + self.eval_within(r, x, values),
+            Minus(l, r) => self.eval_within(l, x, values) - self.eval_within(r, x, values),
+            Mul(l, r) => self.eval_within(l, x, values) * self.eval_within(r, x, values),
+            Div(l, r) => self.eval_within(l, x, values) / self.eval_within(r, x, values),
+            Pow(b, e) => self.eval_within(b, x, values).powf(&self.eval_within(e, x, values)),
+            LogOf(b, a) => self.eval_within(a, x, values).ln() / self.eval_within(b, x, values).ln(),
+            RootOf(n, a) => {
+                let degree = Number::float(self.eval_within(n, x, values));
+                func::Root(degree).eval(self.eval_within(a, x, values))
+            },
+            
+            // The outer function takes a different argument, so its values are not shared.
+            Compose(o, i) => {
+                let inner = self.eval_within(i, x, values);
+                self.eval(o, inner)
+            },
+            Piecewise(l, relation, r, t, o) => {
+                let (left, right) = (self.eval_within(l, x, values), self.eval_within(r, x, values));
+                if relation.compare(left, right) {
+                    self.eval_within(t, x, values)
+                } else {
+                    self.eval_within(o, x, values)
+                }
+            }
+        };
+        
+        values.insert(id, value);
+        value
+    }
+    
+    /// Evaluates a node at a point without rounding.
+    /// Returns None if some step has no exact rational value.
+    pub fn eval_exact(&self, id: NodeId, x: &Number) -> Option<Number> {
+        let mut values = HashMap::new();
+        self.eval_exact_within(id, x, &mut values)
+    }
+    
+    /// Evaluates a node at a point without rounding, remembering the values found so far.
+    fn eval_exact_within(&self, id: NodeId, x: &Number, values: &mut HashMap<NodeId, Option<Number>>)
+            -> Option<Number> {
+        match values.find(&id) {
+            Some(v) => return v.clone(),
+            None => ()
+        }
+        
+        let value = match self.nodes[id] {
+            Leaf(ref f) => exact_leaf(f, x),
+            Plus(l, r) => self.eval_exact_within(l, x, values)
+                .and_then(|a| self.eval_exact_within(r, x, values).map(|b| a + b)),
+            Minus(l, r) => self.eval_exact_within(l, x, values)
+                .and_then(|a| self.eval_exact_within(r, x, values).map(|b| a - b)),
+            Mul(l, r) => self.eval_exact_within(l, x, values)
+                .and_then(|a| self.eval_exact_within(r, x, values).map(|b| a * b)),
+            Div(l, r) => self.eval_exact_within(l, x, values)
+                .and_then(|a| self.eval_exact_within(r, x, values).map(|b| a / b)),
+            Pow(b, e) => self.eval_exact_within(b, x, values)
+                .and_then(|a| self.eval_exact_within(e, x, values).map(|c| a.pow(&c))),
+            Compose(o, i) => self.eval_exact_within(i, x, values).and_then(|inner| self.eval_exact(o, &inner)),
+            Piecewise(l, relation, r, t, o) => {
+                let left = self.eval_exact_within(l, x, values);
+                let right = self.eval_exact_within(r, x, values);
+                match (left, right) {
+                    (Some(a), Some(b)) => if relation.compare(a.to_f64(), b.to_f64()) {
+                        self.eval_exact_within(t, x, values)
+                    } else {
+                        self.eval_exact_within(o, x, values)
+                    },
+                    _ => None
+                }
+            },
+            LogOf(_, _) | RootOf(_, _) => None
+        };
+        
+        // Powers that are too large or irrational become floats.
+        let value = value.and_then(|v| if v.is_exact() { Some(v) } else { None });
+        values.insert(id, value.clone());
+        value
+    }
+    
+    /// Returns a copy of a node.
+    pub fn node(&self, id: NodeId) -> Node {
+        self.nodes[id].clone()
+    }
+    
+    /// Returns the index of a node, adding it if there is no equal one.
+    pub fn intern(&mut self, node: Node) -> NodeId {
+        // NaN is not equal to itself, so non-finite constants are stored as undefined.
+        let node = match node {
+            Leaf(func::Constant(ref c)) if !c.is_finite() => Leaf(func::Undefined),
+            Leaf(func::Power(ref p)) if !p.is_finite() => Leaf(func::Undefined),
+            node => node
+        };
+        
+        match self.index.find(&node) {
+            Some(&id) => return id,
+            None => ()
+        }
+        
+        let id = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.index.insert(node, id);
+        id
+    }
+    
+    /// Returns the value of a constant node.
+    pub fn constant(&self, id: NodeId) -> Option<Number> {
+        match self.nodes[id] {
+            Leaf(func::Constant(ref c)) => Some(c.clone()),
+            _ => None
+        }
+    }
+    
+    /// Checks whether a node is the constant zero.
+    pub fn is_zero(&self, id: NodeId) -> bool {
+        self.constant(id).map_or(false, |c| c.is_zero())
+    }
+    
+    /// Checks whether a node is the constant one.
+    pub fn is_one(&self, id: NodeId) -> bool {
+        self.constant(id).map_or(false, |c| c.is_one())
+    }
+    
+    /// Checks whether a node is the identity function.
+    fn is_identity(&self, id: NodeId) -> bool {
+        match self.nodes[id] {
+            Leaf(func::Power(ref p)) => p.is_one(),
+            _ => false
+        }
+    }
+    
+    /// Builds a sum, dropping zero terms.
+    fn plus(&mut self, l: NodeId, r: NodeId) -> NodeId {
+        if self.is_zero(l) {
+            r
+        } else if self.is_zero(r) {
+            l
+        } else {
+            self.intern(Plus(l, r))
+        }
+    }
+    
+    /// Builds a difference, dropping a zero subtrahend.
+    fn minus(&mut self, l: NodeId, r: NodeId) -> NodeId {
+        if self.is_zero(r) { l } else { self.intern(Minus(l, r)) }
+    }
+    
+    /// Builds a product, dropping factors of one and terms with a zero factor.
+    fn mul(&mut self, l: NodeId, r: NodeId) -> NodeId {
+        if self.is_zero(l) || self.is_one(r) {
+            l
+        } else if self.is_zero(r) || self.is_one(l) {
+            r
+        } else {
+            self.intern(Mul(l, r))
+        }
+    }
+    
+    /// Builds a quotient, dropping a denominator of one.
+    fn div(&mut self, l: NodeId, r: NodeId) -> NodeId {
+        if self.is_one(r) { l } else { self.intern(Div(l, r)) }
+    }
+    
+    /// Builds a composition, dropping the identity function.
+    pub fn compose(&mut self, o: NodeId, i: NodeId) -> NodeId {
+        if self.is_identity(i) {
+            o
+        } else if self.is_identity(o) {
+            i
+        } else {
+            self.intern(Compose(o, i))
+        }
+    }
+    
+    /// Differentiates the node 'id', which is base^exponent, like func::power_rule.
+    fn power_rule(&mut self, id: NodeId, base: NodeId, exponent: NodeId, var: &str) -> NodeId {
+        let d_base = self.partial(base, var);
+        let d_exponent = self.partial(exponent, var);
+        
+        match (self.constant(base), self.constant(exponent)) {
+            // (f^c)' = c * f^(c - 1) * f'
+            (_, Some(c)) => {
+                let lowered = self.intern(Leaf(func::Constant(c - Number::int(1))));
+                let power = self.intern(Pow(base, lowered));
+                let left = self.mul(exponent, power);
+                self.mul(left, d_base)
+            },
+            
+            // ln(a) is not real for a <= 0.
+            (Some(ref a), None) if a.is_negative() || a.is_zero() => self.intern(Leaf(func::Undefined)),
+            
+            // (a^g)' = a^g * ln(a) * g'
+            (Some(a), None) => {
+                let ln = self.intern(Leaf(func::Ln));
+                let a = self.intern(Leaf(func::Constant(a)));
+                let ln_a = self.compose(ln, a);
+                let left = self.mul(id, ln_a);
+                self.mul(left, d_exponent)
+            },
+            
+            // (f^g)' = f^g * (g' * ln(f) + g * f' / f)
+            (None, None) => {
+                let ln = self.intern(Leaf(func::Ln));
+                let ln_base = self.compose(ln, base);
+                let left = self.mul(d_exponent, ln_base);
+                let product = self.mul(exponent, d_base);
+                let right = self.div(product, base);
+                let sum = self.plus(left, right);
+                self.mul(id, sum)
+            }
+        }
+    }
+}
+
+/// Evaluates a function without operands exactly, where its value is rational.
+fn exact_leaf(f: &DiffFunc, x: &Number) -> Option<Number> {
+    match *f {
+        func::Constant(ref c) => Some(c.clone()),
+        func::Power(ref p) => Some(x.pow(p)),
+        func::Abs => Some(x.abs()),
+        
+        // exp(0) = cos(0) = cosh(0) = 1, ln(1) = 0, and odd functions vanish at 0.
+        func::Exp | func::Cos | func::Cosh if x.is_zero() => Some(Number::int(1)),
+        func::Ln if x.is_one() => Some(Number::int(0)),
+        func::Sin | func::Tan | func::Asin | func::Atan | func::Sinh | func::Tanh | func::Asinh | func::Atanh
+            if x.is_zero() => Some(Number::int(0)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use func;
+    use func::{DiffFunc, Constant, Power, Sin, Cos, Undefined};
+    use number::Number;
+    use simplify::simplify_node;
+    use super::{Graph, Mul};
+    
+    fn sin_x() -> DiffFunc {
+        func::Compose { outer: ~Sin, inner: ~Power(Number::int(1)) }
+    }
+    
+    fn square(f: DiffFunc) -> DiffFunc {
+        func::Compose { outer: ~Power(Number::int(2)), inner: ~f }
+    }
+    
+    #[test]
+    fn equal_functions_share_a_node() {
+        let mut graph = Graph::new();
+        let a = graph.insert(&sin_x());
+        let b = graph.insert(&func::Mul { left: ~sin_x(), right: ~sin_x() });
+        assert_eq!(graph.node(b), Mul(a, a));
+        assert_eq!(graph.insert(&sin_x()), a);
+    }
+    
+    #[test]
+    fn partial_derivatives_are_cached() {
+        let mut graph = Graph::new();
+        let f = graph.insert(&func::Mul { left: ~sin_x(), right: ~Power(Number::int(1)) });
+        let df = graph.partial(f, "x");
+        assert_eq!(graph.partial(f, "x"), df);
+    }
+    
+    #[test]
+    fn shared_nodes_evaluate_like_functions() {
+        let f = func::Plus { left: ~sin_x(), right: ~func::Mul { left: ~sin_x(), right: ~Power(Number::int(2)) } };
+        let mut graph = Graph::new();
+        let id = graph.insert(&f);
+        assert_eq!(graph.eval(id, 0.5), f.eval(0.5));
+    }
+    
+    #[test]
+    fn nan_constants_are_undefined() {
+        let mut graph = Graph::new();
+        let nan = Constant(Number::float(Float::nan()));
+        let a = graph.insert(&nan);
+        assert_eq!(graph.insert(&nan), a);
+        assert_eq!(graph.extract(a), Undefined);
+    }
+    
+    #[test]
+    fn simplifying_nan_terminates() {
+        let mut graph = Graph::new();
+        let f = graph.insert(&func::Plus { left: ~Constant(Number::float(Float::nan())), right: ~sin_x() });
+        let s = graph.simplify(f);
+        assert_eq!(graph.simplify(s), s);
+    }
+    
+    #[test]
+    fn simplification_reaches_a_fixed_point() {
+        // sin(x)^2 + cos(x)^2 = 1
+        let mut graph = Graph::new();
+        let cos_x = func::Compose { outer: ~Cos, inner: ~Power(Number::int(1)) };
+        let f = graph.insert(&func::Plus { left: ~square(sin_x()), right: ~square(cos_x) });
+        let s = simplify_node(&mut graph, f);
+        assert_eq!(graph.extract(s), Constant(Number::int(1)));
+    }
+}
@@ -4,6 +4,8 @@
 #[comment = "Derivative calculator"];
 #[feature(struct_variant)];
 
+extern mod extra;
+
 use std::io::buffered::BufferedReader;
 use std::io::stdin;
 use std::io::stdio::flush;
@@ -12,24 +14,159 @@ use std::io::mem::MemReader;
 use func::DiffFunc;
 use tokenizer::{invalid_token, Ignore};
 use parser::Parser;
-use simplify::Simplify;
+use simplify::{Simplify, derivative_n};
+use statement::{Statement, Derive, Evaluate, Series, Integrate, Quadrature, Solve, SolveBetween, Analyze,
+                Define, Assign};
+use pretty::{Format, Text, render};
+use series::taylor;
+use integrate::{integrate, verify};
+use session::{Session, Function, Variable};
+use analyze::analyze;
 
+mod analyze;
+mod art;
+mod canon;
+mod codegen;
+mod dag;
 mod func;
+mod integrate;
+mod latex;
+mod mathml;
 mod monad;
+mod number;
 mod parser;
+mod poly;
+mod pretty;
+mod quadrature;
+mod rational;
+mod series;
+mod session;
+mod statement;
 mod tokenizer;
 mod simplify;
+mod solver;
 
-/// Parse a string into a function.
-fn parse(s: &str) -> Result<~DiffFunc, ~str> {
+/// Parse a string into a statement.
+fn parse(s: &str, session: &Session) -> Result<~Statement, ~str> {
     let stream = MemReader::new(s.as_bytes().to_owned());
-    Parser::parse(stream)
+    Parser::parse(stream, session)
+}
+
+/// Parses and executes a string.
+fn interpret(s: &str, format: &mut Format, session: &mut Session) {
+    // Commands start with a colon.
+    if s.trim_left().starts_with(":") {
+        return command(s, format)
+    }
+    
+    match parse(s, &*session) {
+        Ok(stmt) => execute(stmt, *format, session),
+        Err(s)   => println!("Error: {}", s)
+    }
+}
+
+/// Runs a command, like ':format latex'.
+fn command(s: &str, format: &mut Format) {
+    let words: ~[&str] = s.words().collect();
+    match words[0] {
+        ":format" if words.len() == 2 => match from_str::<Format>(words[1]) {
+            Some(f) => *format = f,
+            None    => println!("Unknown format: {}", words[1])
+        },
+        ":format" => println!("Usage: :format text|latex|mathml|unicode|rust|c"),
+        _ => println!("Unknown command: {}", words[0])
+    }
+}
+
+/// Executes a statement.
+fn execute(stmt: ~Statement, format: Format, session: &mut Session) {
+    match *stmt {
+        // Print the derivative.
+        Derive { func: ref f, var: ref var, order: n } =>
+            print_func(&derivative_n(&**f, var.as_slice(), n), format),
+        
+        // Print the value of the function and its derivative at a point.
+        Evaluate { func: ref f, var: ref var, order: n, point: x } => match f.free_variable() {
+            // Only 'x' is given a value.
+            Some(v) => println!("Error: Unbound variable {}.", v),
+            None => {
+                let df = derivative_n(&**f, var.as_slice(), n);
+                println!("f({}) = {}", x, f.simplify().eval(x));
+                if n == 1 {
+                    println!("df/d{}({}) = {}", *var, x, df.eval(x));
+                } else {
+                    println!("d{}f/d{}{}({}) = {}", n, *var, n, x, df.eval(x));
+                }
+            }
+        },
+        
+        // Print the Taylor polynomial.
+        Series { func: ref f, degree: n, point: a } => match taylor(&**f, a, n) {
+            Ok(p)  => print_func(&p, format),
+            Err(s) => println!("Error: {}", s)
+        },
+        
+        // Print an antiderivative, checking it by differentiating it back.
+        Integrate { func: ref f } => match integrate(&**f) {
+            Some(g) => {
+                print_func(&g, format);
+                if !verify(&**f, &g) {
+                    println!("Warning: the derivative of the result does not simplify to the function.");
+                }
+            },
+            None => println!("Error: Cannot integrate {}.", f.to_str("x"))
+        },
+        
+        // Print the definite integral with its error estimate.
+        Quadrature { func: ref f, from: a, to: b } => match f.free_variable() {
+            Some(v) => println!("Error: Unbound variable {}.", v),
+            None => match quadrature::integrate(&f.simplify(), a, b) {
+                Ok(e)  => println!("{} (error estimate {})", e.value, e.error),
+                Err(s) => println!("Error: {}", s)
+            }
+        },
+        
+        // Print a root of the function.
+        Solve { func: ref f, guess: x } => print_root(solver::newton(&f.simplify(), x)),
+        SolveBetween { func: ref f, from: a, to: b } => print_root(solver::bracketed(&f.simplify(), a, b)),
+        
+        // Print the critical points and where the function increases or decreases.
+        Analyze { func: ref f, from: a, to: b } => match analyze(&f.simplify(), a, b) {
+            Ok(analysis) => {
+                if analysis.critical_points.is_empty() {
+                    println!("No critical points between {} and {}.", a, b);
+                }
+                for p in analysis.critical_points.iter() {
+                    println!("{} at x = {}, f(x) = {}", p.kind.to_str(), p.x, p.value);
+                }
+                for x in analysis.discontinuities.iter() {
+                    println!("Discontinuity at x = {}", *x);
+                }
+                for i in analysis.intervals.iter() {
+                    println!("{} on [{}, {}]", i.direction.to_str(), i.from, i.to);
+                }
+            },
+            Err(s) => println!("Error: {}", s)
+        },
+        
+        // Remember a function or a variable for later statements.
+        Define { name: ref name, func: ref f } => session.define(name.clone(), Function((**f).clone())),
+        Assign { name: ref name, value: ref v } => session.define(name.clone(), Variable((**v).clone()))
+    }
+}
+
+/// Prints a function in the given format.
+fn print_func(f: &DiffFunc, format: Format) {
+    match render(f, format) {
+        Ok(s)  => println!("{}", s),
+        Err(s) => println!("Error: {}", s)
+    }
 }
 
-/// Parses a string and computes its derivative.
-fn interpret(s: &str) {
-    match parse(s) {
-        Ok(f)  => println!("{}", f.simplify().derivative().simplify().to_str("x")),
+/// Prints the result of root finding.
+fn print_root(root: Result<f64, ~str>) {
+    match root {
+        Ok(x)  => println!("x = {}", x),
         Err(s) => println!("Error: {}", s)
     }
 }
@@ -37,6 +174,8 @@ fn interpret(s: &str) {
 /// Runs the read_line-parse loop.
 fn run() {
     let mut stdin = BufferedReader::new(stdin());
+    let mut format = Text;
+    let mut session = Session::new();
 
     loop {
         print!("> ");
@@ -44,7 +183,7 @@ fn run() {
 
         let line = stdin.read_line();
         match line {
-            Some(s) => interpret(s),
+            Some(s) => interpret(s, &mut format, &mut session),
             None    => break
         }
     }
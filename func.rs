@@ -1,53 +1,127 @@
 //! Differentiable functions.
 
+use pretty;
+use number::Number;
+
 /// An Differentiable function.
-#[deriving(Clone, Eq)]
+/// Functions take an implicit argument, which at the top level is the variable 'x'.
+/// Other variables are represented by Var and do not depend on the argument.
+#[deriving(Clone, Eq, IterBytes)]
 pub enum DiffFunc {
     Exp,
     Ln,
+    Log(Number),
+    Root(Number),
     Sin,
     Cos,
+    Tan,
+    Cot,
+    Asin,
+    Acos,
+    Atan,
+    Acot,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Abs,
+    Sign,
     
-    Constant(f64),
-    Power(f64),
+    Constant(Number),
+    Pi,
+    E,
+    Power(Number),
+    Var(~str),
+    Undefined,
     
     Plus  { left: ~DiffFunc, right: ~DiffFunc },
     Minus { left: ~DiffFunc, right: ~DiffFunc },
     Mul   { left: ~DiffFunc, right: ~DiffFunc },
     Div   { left: ~DiffFunc, right: ~DiffFunc },
-    Compose { outer: ~DiffFunc, inner: ~DiffFunc }
+    Pow   { base: ~DiffFunc, exponent: ~DiffFunc },
+    LogOf { base: ~DiffFunc, arg: ~DiffFunc },
+    RootOf { degree: ~DiffFunc, arg: ~DiffFunc },
+    Compose { outer: ~DiffFunc, inner: ~DiffFunc },
+    Piecewise { cond: Condition, then: ~DiffFunc, otherwise: ~DiffFunc }
+}
+
+/// A relation between two values.
+#[deriving(Clone, Eq, IterBytes)]
+pub enum Relation {
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+/// A condition of a piecewise function.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct Condition {
+    left: ~DiffFunc,
+    relation: Relation,
+    right: ~DiffFunc
 }
 
 impl DiffFunc {
     /// Converts a function to a string.
     pub fn to_str(&self, arg: &str) -> ~str {
-        match *self {
-            Exp => format!("exp({})", arg),
-            Ln  => format!("ln({})", arg),
-            Sin => format!("sin({})", arg),
-            Cos => format!("cos({})", arg),
-            
-            Constant(f) => if f >= 0.0 { format!("{}", f) } else { format!("({})", f) },
-            Power(f) => if f == 1.0 { arg.to_owned() } else { format!("({}^{})", arg, f) },
-            
-            Plus { left: ref l, right: ref r } => format!("({} + {})", l.to_str(arg), r.to_str(arg)),
-            Minus { left: ref l, right: ref r } => format!("({} - {})", l.to_str(arg), r.to_str(arg)),
-            Mul { left: ref l, right: ref r } => format!("({} * {})", l.to_str(arg), r.to_str(arg)),
-            Div { left: ref l, right: ref r } => format!("({} / {})", l.to_str(arg), r.to_str(arg)),
-            Compose { outer: ref o, inner: ref i } => o.to_str(i.to_str(arg))
-        }
+        pretty::lower(self, &pretty::Sym(arg.to_owned())).to_text()
     }
     
     /// Computes the derivative.
     pub fn derivative(&self) -> DiffFunc {
         match *self {
             Exp => Exp,
-            Ln  => Power(-1.0),
+            Ln  => Power(Number::int(-1)),
+            Log(ref b) => Div {
+                left: ~Constant(Number::int(1)),
+                right: ~Mul { left: ~Compose { outer: ~Ln, inner: ~Constant(b.clone()) }, right: ~Power(Number::int(1)) }
+            },
+            Root(ref n) => Div {
+                left: ~Constant(Number::int(1)),
+                right: ~Mul {
+                    left: ~Constant(n.clone()),
+                    right: ~Compose { outer: ~Power(*n - Number::int(1)), inner: ~Root(n.clone()) }
+                }
+            },
             Sin => Cos,
-            Cos => Mul { left: ~Constant(-1.0), right: ~Sin },
+            Cos => Mul { left: ~Constant(Number::int(-1)), right: ~Sin },
+            Tan => Div { left: ~Constant(Number::int(1)), right: ~Compose { outer: ~Power(Number::int(2)), inner: ~Cos } },
+            Cot => Div { left: ~Constant(Number::int(-1)), right: ~Compose { outer: ~Power(Number::int(2)), inner: ~Sin } },
+            Asin => Compose {
+                outer: ~Power(Number::ratio(-1, 2)),
+                inner: ~Minus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) }
+            },
+            Acos => Mul {
+                left: ~Constant(Number::int(-1)),
+                right: ~Compose {
+                    outer: ~Power(Number::ratio(-1, 2)),
+                    inner: ~Minus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) }
+                }
+            },
+            Atan => Div { left: ~Constant(Number::int(1)), right: ~Plus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) } },
+            Acot => Div { left: ~Constant(Number::int(-1)), right: ~Plus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) } },
+            Sinh => Cosh,
+            Cosh => Sinh,
+            Tanh => Div { left: ~Constant(Number::int(1)), right: ~Compose { outer: ~Power(Number::int(2)), inner: ~Cosh } },
+            Asinh => Compose {
+                outer: ~Power(Number::ratio(-1, 2)),
+                inner: ~Plus { left: ~Power(Number::int(2)), right: ~Constant(Number::int(1)) }
+            },
+            Acosh => Compose {
+                outer: ~Power(Number::ratio(-1, 2)),
+                inner: ~Minus { left: ~Power(Number::int(2)), right: ~Constant(Number::int(1)) }
+            },
+            Atanh => Div { left: ~Constant(Number::int(1)), right: ~Minus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) } },
+            Abs => by_sign(Constant(Number::int(-1)), Constant(Number::int(1))),
+            Sign => by_sign(Constant(Number::int(0)), Constant(Number::int(0))),
             
-            Constant(_) => Constant(0.0),
-            Power(f) => Mul { left: ~Constant(f), right: ~Power(f - 1.0) },
+            Constant(_) | Pi | E => Constant(Number::int(0)),
+            Power(ref f) => Mul { left: ~Constant(f.clone()), right: ~Power(*f - Number::int(1)) },
+            Var(_) => Constant(Number::int(0)),
+            Undefined => Undefined,
             
             Plus { left: ref l, right: ref r } => Plus { left: ~l.derivative(), right: ~r.derivative() },
             Minus { left: ref l, right: ref r } => Minus { left: ~l.derivative(), right: ~r.derivative() },
@@ -61,17 +135,293 @@ impl DiffFunc {
                     right: ~Mul { left: l.clone(), right: ~r.derivative() }
                 },
                 right: ~Compose {
-                    outer: ~Power(2.0),
+                    outer: ~Power(Number::int(2)),
                     inner: r.clone()
                 }
             },
+            Pow { base: ref b, exponent: ref e } => power_rule(b, e, b.derivative(), e.derivative()),
+            LogOf { base: ref b, arg: ref a } => log_quotient(b, a).derivative(),
+            RootOf { degree: ref n, arg: ref a } => root_power(n, a).derivative(),
             Compose { outer: ref o, inner: ref i } => Mul {
                 left: ~Compose {
                     outer: ~o.derivative(),
                     inner: i.clone()
                 },
                 right: ~i.derivative()
+            },
+            Piecewise { cond: ref c, then: ref t, otherwise: ref o } => piecewise_derivative(c, t.derivative(), o.derivative())
+        }
+    }
+    
+    /// Computes the partial derivative with respect to a variable.
+    pub fn partial(&self, var: &str) -> DiffFunc {
+        // 'x' is the implicit argument.
+        if var == "x" {
+            return self.derivative()
+        }
+        
+        match *self {
+            // Built-in functions depend on the argument only.
+            Exp | Ln | Log(_) | Root(_) => Constant(Number::int(0)),
+            Sin | Cos | Tan | Cot | Asin | Acos | Atan | Acot => Constant(Number::int(0)),
+            Sinh | Cosh | Tanh | Asinh | Acosh | Atanh | Abs | Sign => Constant(Number::int(0)),
+            
+            Constant(_) | Pi | E => Constant(Number::int(0)),
+            Power(_) => Constant(Number::int(0)),
+            Var(ref name) => if name.as_slice() == var { Constant(Number::int(1)) } else { Constant(Number::int(0)) },
+            Undefined => Undefined,
+            
+            Plus { left: ref l, right: ref r } => Plus { left: ~l.partial(var), right: ~r.partial(var) },
+            Minus { left: ref l, right: ref r } => Minus { left: ~l.partial(var), right: ~r.partial(var) },
+            Mul { left: ref l, right: ref r } => Plus {
+                left: ~Mul { left: ~l.partial(var), right: r.clone() },
+                right: ~Mul { left: l.clone(), right: ~r.partial(var) }
+            },
+            Div { left: ref l, right: ref r } => Div {
+                left: ~Minus {
+                    left: ~Mul { left: ~l.partial(var), right: r.clone() },
+                    right: ~Mul { left: l.clone(), right: ~r.partial(var) }
+                },
+                right: ~Compose {
+                    outer: ~Power(Number::int(2)),
+                    inner: r.clone()
+                }
+            },
+            Pow { base: ref b, exponent: ref e } => power_rule(b, e, b.partial(var), e.partial(var)),
+            LogOf { base: ref b, arg: ref a } => log_quotient(b, a).partial(var),
+            RootOf { degree: ref n, arg: ref a } => root_power(n, a).partial(var),
+            Compose { outer: ref o, inner: ref i } => {
+                let chain = Mul {
+                    left: ~Compose {
+                        outer: ~o.derivative(),
+                        inner: i.clone()
+                    },
+                    right: ~i.partial(var)
+                };
+                
+                // The outer function can use the variable too, like f(t) = t y.
+                let d_outer = o.partial(var);
+                if d_outer.is_zero() {
+                    chain
+                } else {
+                    Plus { left: ~chain, right: ~Compose { outer: ~d_outer, inner: i.clone() } }
+                }
+            },
+            Piecewise { cond: ref c, then: ref t, otherwise: ref o } => piecewise_derivative(c, t.partial(var), o.partial(var))
+        }
+    }
+    
+    /// Evaluates the function at a point.
+    /// Variables other than 'x' are unbound and evaluate to NaN.
+    pub fn eval(&self, x: f64) -> f64 {
+        match *self {
+            Exp => x.exp(),
+            Ln  => x.ln(),
+            Log(ref b) => x.ln() / b.to_f64().ln(),
+            Root(ref n) => {
+                let n = n.to_f64();
+                
+                // Odd roots are defined for negative numbers.
+                let odd = n == n.floor() && n % 2.0 == 1.0;
+                if x < 0.0 && odd { -(-x).powf(&(1.0 / n)) } else { x.powf(&(1.0 / n)) }
+            },
+            Sin => x.sin(),
+            Cos => x.cos(),
+            Tan => x.tan(),
+            Cot => 1.0 / x.tan(),
+            Asin => x.asin(),
+            Acos => x.acos(),
+            Atan => x.atan(),
+            Acot => { let half_pi: f64 = Real::frac_pi_2(); half_pi - x.atan() },
+            Sinh => x.sinh(),
+            Cosh => x.cosh(),
+            Tanh => x.tanh(),
+            Asinh => x.asinh(),
+            Acosh => x.acosh(),
+            Atanh => x.atanh(),
+            Abs => x.abs(),
+            Sign => if x > 0.0 { 1.0 } else if x < 0.0 { -1.0 } else { x },
+            
+            Constant(ref f) => f.to_f64(),
+            Pi => Real::pi(),
+            E => Real::e(),
+            Power(ref f) => x.powf(&f.to_f64()),
+            Var(_) => Float::nan(),
+            Undefined => Float::nan(),
+            
+            Plus { left: ref l, right: ref r } => l.eval(x) + r.eval(x),
+            Minus { left: ref l, right: ref r } => l.eval(x) - r.eval(x),
+            Mul { left: ref l, right: ref r } => l.eval(x) * r.eval(x),
+            Div { left: ref l, right: ref r } => l.eval(x) / r.eval(x),
+            Pow { base: ref b, exponent: ref e } => b.eval(x).powf(&e.eval(x)),
+            LogOf { base: ref b, arg: ref a } => a.eval(x).ln() / b.eval(x).ln(),
+            RootOf { degree: ref n, arg: ref a } => Root(Number::float(n.eval(x))).eval(a.eval(x)),
+            Compose { outer: ref o, inner: ref i } => o.eval(i.eval(x)),
+            Piecewise { cond: ref c, then: ref t, otherwise: ref o } =>
+                if c.holds(x) { t.eval(x) } else { o.eval(x) }
+        }
+    }
+    
+    /// Returns a variable other than the argument that the function uses, if there is one.
+    pub fn free_variable(&self) -> Option<~str> {
+        match *self {
+            Var(ref name) => Some(name.clone()),
+            Plus { left: ref l, right: ref r } | Minus { left: ref l, right: ref r } |
+            Mul { left: ref l, right: ref r } | Div { left: ref l, right: ref r } |
+            Pow { base: ref l, exponent: ref r } | LogOf { base: ref l, arg: ref r } |
+            RootOf { degree: ref l, arg: ref r } | Compose { outer: ref l, inner: ref r } =>
+                l.free_variable().or_else(|| r.free_variable()),
+            Piecewise { cond: ref c, then: ref t, otherwise: ref o } =>
+                c.left.free_variable()
+                    .or_else(|| c.right.free_variable())
+                    .or_else(|| t.free_variable())
+                    .or_else(|| o.free_variable()),
+            _ => None
+        }
+    }
+    
+    /// Checks whether the function depends on its argument.
+    pub fn depends_on_x(&self) -> bool {
+        match *self {
+            Constant(_) | Pi | E | Var(_) => false,
+            Plus { left: ref l, right: ref r } | Minus { left: ref l, right: ref r } |
+            Mul { left: ref l, right: ref r } | Div { left: ref l, right: ref r } =>
+                l.depends_on_x() || r.depends_on_x(),
+            Pow { base: ref b, exponent: ref e } => b.depends_on_x() || e.depends_on_x(),
+            LogOf { base: ref b, arg: ref a } => b.depends_on_x() || a.depends_on_x(),
+            RootOf { degree: ref n, arg: ref a } => n.depends_on_x() || a.depends_on_x(),
+            Compose { outer: ref o, inner: ref i } => o.depends_on_x() && i.depends_on_x(),
+            _ => true
+        }
+    }
+    
+    /// Checks whether the function is the constant zero.
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Constant(ref c) => c.is_zero(),
+            _ => false
+        }
+    }
+    
+    /// Checks whether the function is the constant one.
+    pub fn is_one(&self) -> bool {
+        match *self {
+            Constant(ref c) => c.is_one(),
+            _ => false
+        }
+    }
+}
+
+impl Relation {
+    /// Returns the symbol of the relation.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Lt => "<",
+            Le => "<=",
+            Gt => ">",
+            Ge => ">="
+        }
+    }
+    
+    /// Compares two values.
+    pub fn compare(&self, a: f64, b: f64) -> bool {
+        match *self {
+            Lt => a < b,
+            Le => a <= b,
+            Gt => a > b,
+            Ge => a >= b
+        }
+    }
+}
+
+impl Condition {
+    /// Checks whether the condition holds at a point.
+    pub fn holds(&self, x: f64) -> bool {
+        self.relation.compare(self.left.eval(x), self.right.eval(x))
+    }
+}
+
+/// Differentiates a piecewise function given the derivatives of its branches.
+/// Like for abs, the derivative is undefined where the two sides of the condition are equal.
+fn piecewise_derivative(c: &Condition, d_then: DiffFunc, d_otherwise: DiffFunc) -> DiffFunc {
+    let (below, above) = match c.relation {
+        Lt | Le => (d_then, d_otherwise),
+        Gt | Ge => (d_otherwise, d_then)
+    };
+    Piecewise {
+        cond: Condition { left: c.left.clone(), relation: Lt, right: c.right.clone() },
+        then: ~below,
+        otherwise: ~Piecewise {
+            cond: Condition { left: c.left.clone(), relation: Gt, right: c.right.clone() },
+            then: ~above,
+            otherwise: ~Undefined
+        }
+    }
+}
+
+/// Builds a function of the sign of the argument, undefined at 0.
+fn by_sign(negative: DiffFunc, positive: DiffFunc) -> DiffFunc {
+    Piecewise {
+        cond: Condition { left: ~Power(Number::int(1)), relation: Lt, right: ~Constant(Number::int(0)) },
+        then: ~negative,
+        otherwise: ~Piecewise {
+            cond: Condition { left: ~Power(Number::int(1)), relation: Gt, right: ~Constant(Number::int(0)) },
+            then: ~positive,
+            otherwise: ~Undefined
+        }
+    }
+}
+
+/// Differentiates base^exponent given the derivatives of the base and the exponent.
+fn power_rule(base: &~DiffFunc, exponent: &~DiffFunc, d_base: DiffFunc, d_exponent: DiffFunc) -> DiffFunc {
+    match (&**base, &**exponent) {
+        // (f^c)' = c * f^(c - 1) * f'
+        (_, &Constant(ref c)) => Mul {
+            left: ~Mul {
+                left: ~Constant(c.clone()),
+                right: ~Pow { base: base.clone(), exponent: ~Constant(*c - Number::int(1)) }
+            },
+            right: ~d_base
+        },
+        
+        // ln(a) is not real for a <= 0.
+        (&Constant(ref a), _) if a.is_negative() || a.is_zero() => Undefined,
+        
+        // (a^g)' = a^g * ln(a) * g'
+        (&Constant(ref a), _) => Mul {
+            left: ~Mul {
+                left: ~Pow { base: base.clone(), exponent: exponent.clone() },
+                right: ~Compose { outer: ~Ln, inner: ~Constant(a.clone()) }
+            },
+            right: ~d_exponent
+        },
+        
+        // (f^g)' = f^g * (g' * ln(f) + g * f' / f)
+        _ => Mul {
+            left: ~Pow { base: base.clone(), exponent: exponent.clone() },
+            right: ~Plus {
+                left: ~Mul {
+                    left: ~d_exponent,
+                    right: ~Compose { outer: ~Ln, inner: base.clone() }
+                },
+                right: ~Div {
+                    left: ~Mul { left: exponent.clone(), right: ~d_base },
+                    right: base.clone()
+                }
             }
         }
     }
 }
+
+/// Writes log(b, f) as ln(f) / ln(b).
+fn log_quotient(base: &~DiffFunc, arg: &~DiffFunc) -> DiffFunc {
+    Div {
+        left: ~Compose { outer: ~Ln, inner: arg.clone() },
+        right: ~Compose { outer: ~Ln, inner: base.clone() }
+    }
+}
+
+/// Writes root(n, f) as f^(1 / n).
+fn root_power(degree: &~DiffFunc, arg: &~DiffFunc) -> DiffFunc {
+    Pow { base: arg.clone(), exponent: ~Div { left: ~Constant(Number::int(1)), right: degree.clone() } }
+}
@@ -0,0 +1,304 @@
+//! Symbolic integration.
+//! Antiderivatives are found for polynomials, built-in functions of linear arguments,
+//! and products of a polynomial with one such function, which are integrated by parts.
+
+use func::{DiffFunc, Exp, Ln, Log, Root, Sin, Cos, Tan, Cot, Asin, Acos, Atan, Sinh, Cosh, Tanh, Abs, Sign};
+use func::{Constant, Power, Plus, Minus, Mul, Div, Compose};
+use dag::Graph;
+use canon::{canonicalize, factors, product};
+use rational::terms;
+use number::Number;
+use poly::Poly;
+use simplify::{Simplify, derivative_n};
+
+/// Computes an antiderivative with respect to x, without the constant of integration.
+/// Returns None if the function is not of a supported form.
+pub fn integrate(f: &DiffFunc) -> Option<DiffFunc> {
+    antiderivative(&f.simplify()).map(|g| g.simplify())
+}
+
+/// Checks that the derivative of an antiderivative simplifies to the function.
+pub fn verify(f: &DiffFunc, antiderivative: &DiffFunc) -> bool {
+    let difference = Minus { left: ~derivative_n(antiderivative, "x", 1), right: ~f.clone() };
+    difference.simplify().is_zero()
+}
+
+/// Integrates a simplified function.
+fn antiderivative(f: &DiffFunc) -> Option<DiffFunc> {
+    // Polynomials are integrated term by term.
+    match Poly::from_func(f) {
+        Some(p) => return Some(p.integral().to_func()),
+        None => ()
+    }
+    
+    match *f {
+        Plus { left: ref l, right: ref r } =>
+            antiderivative(&**l).and_then(|l| antiderivative(&**r).map(|r| Plus { left: ~l, right: ~r })),
+        Minus { left: ref l, right: ref r } =>
+            antiderivative(&**l).and_then(|l| antiderivative(&**r).map(|r| Minus { left: ~l, right: ~r })),
+        
+        // Fractions with a sum as the numerator are split into a sum of fractions.
+        Div { left: ref n, right: ref d } if is_sum(&**n) => {
+            let mut graph = Graph::new();
+            let n = graph.insert(&**n);
+            let mut result = Constant(Number::int(0));
+            for &t in terms(&mut graph, n).iter() {
+                match antiderivative(&canonicalize(&Div { left: ~graph.extract(t), right: d.clone() })) {
+                    Some(g) => result = Plus { left: ~result, right: ~g },
+                    None => return None
+                }
+            }
+            Some(result)
+        },
+        
+        _ => product_integral(f)
+    }
+}
+
+/// Integrates a product, taking the factors that do not depend on x out.
+fn product_integral(f: &DiffFunc) -> Option<DiffFunc> {
+    let mut graph = Graph::new();
+    let f = graph.insert(f);
+    let (c, fs) = factors(&mut graph, f);
+    let mut constant = ~[];
+    let mut variable = ~[];
+    for (base, e) in fs.move_iter() {
+        let g = graph.extract(base);
+        if g.depends_on_x() {
+            variable.push((g, e));
+        } else {
+            constant.push((base, e));
+        }
+    }
+    
+    let integral = match variable.len() {
+        0 => Some(x()),
+        1 => {
+            let (base, e) = variable.pop();
+            power_integral(&base, &e)
+        },
+        _ => by_parts(variable)
+    };
+    let constant = product(&mut graph, c, constant);
+    integral.map(|g| times(graph.extract(constant), g))
+}
+
+/// Integrates a function raised to a constant power.
+fn power_integral(base: &DiffFunc, e: &Number) -> Option<DiffFunc> {
+    match linear(base) {
+        // (ax + b)^e = (ax + b)^(e + 1) / (a (e + 1)), or ln|ax + b| / a for e = -1
+        Some(a) => {
+            let raised = *e + Number::int(1);
+            Some(if raised.is_zero() {
+                times(Constant(Number::int(1) / a), ln_abs(base.clone()))
+            } else {
+                times(Constant(Number::int(1) / (a * raised)), Compose { outer: ~Power(raised), inner: ~base.clone() })
+            })
+        },
+        None => if e.is_one() { elementary(base) } else { None }
+    }
+}
+
+/// Integrates a built-in function applied to x or to a linear function of x.
+fn elementary(g: &DiffFunc) -> Option<DiffFunc> {
+    match *g {
+        // The integral of f(ax + b) is F(ax + b) / a.
+        Compose { outer: ref o, inner: ref i } => linear(&**i).and_then(|a|
+            table(&**o).map(|h| times(Constant(Number::int(1) / a), Compose { outer: ~h, inner: i.clone() }))
+        ),
+        _ => table(g)
+    }
+}
+
+/// Returns the antiderivative of a built-in function.
+fn table(f: &DiffFunc) -> Option<DiffFunc> {
+    let result = match *f {
+        Exp => Exp,
+        
+        // x ln(x) - x
+        Ln => Minus { left: ~Mul { left: ~x(), right: ~Ln }, right: ~x() },
+        Log(ref b) => Div {
+            left: ~Minus { left: ~Mul { left: ~x(), right: ~Ln }, right: ~x() },
+            right: ~Compose { outer: ~Ln, inner: ~Constant(b.clone()) }
+        },
+        
+        Sin => Mul { left: ~Constant(Number::int(-1)), right: ~Cos },
+        Cos => Sin,
+        Tan => Mul { left: ~Constant(Number::int(-1)), right: ~ln_abs(Cos) },
+        Cot => ln_abs(Sin),
+        Sinh => Cosh,
+        Cosh => Sinh,
+        Tanh => Compose { outer: ~Ln, inner: ~Cosh },
+        
+        // x atan(x) - ln(1 + x^2) / 2
+        Atan => Minus {
+            left: ~Mul { left: ~x(), right: ~Atan },
+            right: ~Mul {
+                left: ~Constant(Number::ratio(1, 2)),
+                right: ~Compose {
+                    outer: ~Ln,
+                    inner: ~Plus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) }
+                }
+            }
+        },
+        
+        // x asin(x) + sqrt(1 - x^2), x acos(x) - sqrt(1 - x^2)
+        Asin => Plus { left: ~Mul { left: ~x(), right: ~Asin }, right: ~sqrt_one_minus_square() },
+        Acos => Minus { left: ~Mul { left: ~x(), right: ~Acos }, right: ~sqrt_one_minus_square() },
+        
+        // x |x| / 2
+        Abs => Mul { left: ~Constant(Number::ratio(1, 2)), right: ~Mul { left: ~x(), right: ~Abs } },
+        Sign => Abs,
+        
+        Power(ref p) => return power_integral(&x(), p),
+        Root(ref n) if n.is_integer() && *n >= Number::int(2) => return power_integral(&x(), &(Number::int(1) / *n)),
+        _ => return None
+    };
+    Some(result)
+}
+
+/// Integrates a polynomial times one other factor by parts.
+fn by_parts(fs: ~[(DiffFunc, Number)]) -> Option<DiffFunc> {
+    let mut p = Poly::constant(Number::int(1));
+    let mut others = ~[];
+    for (base, e) in fs.move_iter() {
+        let polynomial = match (Poly::from_func(&base), e.to_int()) {
+            (Some(q), Some(n)) => if n > 0 { Some(q.pow(n as uint)) } else { None },
+            _ => None
+        };
+        match polynomial {
+            Some(q) => p = p * q,
+            None => others.push((base, e))
+        }
+    }
+    
+    if others.len() != 1 || p.degree() == Some(0) {
+        return None
+    }
+    let (g, e) = others.pop();
+    if !e.is_one() {
+        return None
+    }
+    
+    let outer = match outer_function(&g) {
+        Some(o) => o.clone(),
+        None => return None
+    };
+    match outer {
+        // The function is integrated and the polynomial differentiated: p G - integral of p' G
+        Exp | Sin | Cos | Sinh | Cosh => elementary(&g).and_then(|integral| {
+            let rest = canonicalize(&Mul { left: ~p.derivative().to_func(), right: ~integral.clone() });
+            antiderivative(&rest).map(|r| Minus { left: ~Mul { left: ~p.to_func(), right: ~integral }, right: ~r })
+        }),
+        
+        // The polynomial is integrated and the function differentiated: P g - integral of P g'
+        Ln | Log(_) | Atan | Asin | Acos => {
+            let integral = p.integral().to_func();
+            let rest = canonicalize(&Mul { left: ~integral.clone(), right: ~g.derivative() });
+            antiderivative(&rest).map(|r| Minus { left: ~Mul { left: ~integral, right: ~g }, right: ~r })
+        },
+        
+        _ => None
+    }
+}
+
+/// Returns the built-in function of a factor that is applied to x or to a linear function of x.
+fn outer_function<'a>(g: &'a DiffFunc) -> Option<&'a DiffFunc> {
+    match *g {
+        Compose { outer: ref o, inner: ref i } => if linear(&**i).is_some() { Some(&**o) } else { None },
+        _ => Some(g)
+    }
+}
+
+/// Returns the slope of a linear function of x.
+fn linear(f: &DiffFunc) -> Option<Number> {
+    Poly::from_func(f).and_then(|p| if p.degree() == Some(1) { Some(p.coefficient(1)) } else { None })
+}
+
+/// Checks whether a function is a sum or a difference.
+fn is_sum(f: &DiffFunc) -> bool {
+    match *f {
+        Plus { left: _, right: _ } | Minus { left: _, right: _ } => true,
+        _ => false
+    }
+}
+
+/// Multiplies two functions, leaving out a factor of one.
+fn times(a: DiffFunc, b: DiffFunc) -> DiffFunc {
+    if a.is_one() { b } else { Mul { left: ~a, right: ~b } }
+}
+
+/// The identity function x.
+fn x() -> DiffFunc {
+    Power(Number::int(1))
+}
+
+/// Builds ln|f|.
+fn ln_abs(f: DiffFunc) -> DiffFunc {
+    Compose { outer: ~Ln, inner: ~Compose { outer: ~Abs, inner: ~f } }
+}
+
+/// Builds sqrt(1 - x^2).
+fn sqrt_one_minus_square() -> DiffFunc {
+    Compose {
+        outer: ~Power(Number::ratio(1, 2)),
+        inner: ~Minus { left: ~Constant(Number::int(1)), right: ~Power(Number::int(2)) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use func::{DiffFunc, Exp, Ln, Cos, Constant, Power, Plus, Mul, Compose};
+    use number::Number;
+    use super::{integrate, verify, x};
+    
+    /// Checks that the function has an antiderivative whose derivative is the function again.
+    fn round_trip(f: DiffFunc) {
+        match integrate(&f) {
+            Some(g) => assert!(verify(&f, &g)),
+            None => fail!("Cannot integrate {}.", f.to_str("x"))
+        }
+    }
+    
+    #[test]
+    fn polynomials() {
+        // 3x^2 + 2x
+        round_trip(Plus {
+            left: ~Mul { left: ~Constant(Number::int(3)), right: ~Power(Number::int(2)) },
+            right: ~Mul { left: ~Constant(Number::int(2)), right: ~x() }
+        });
+    }
+    
+    #[test]
+    fn built_in_functions() {
+        round_trip(Cos);
+        round_trip(Exp);
+    }
+    
+    #[test]
+    fn linear_arguments() {
+        // exp(2x)
+        round_trip(Compose { outer: ~Exp, inner: ~Mul { left: ~Constant(Number::int(2)), right: ~x() } });
+    }
+    
+    #[test]
+    fn powers_of_linear_functions() {
+        // (x + 1)^-2
+        let sum = Plus { left: ~x(), right: ~Constant(Number::int(1)) };
+        round_trip(Compose { outer: ~Power(Number::int(-2)), inner: ~sum });
+    }
+    
+    #[test]
+    fn polynomials_times_functions_by_parts() {
+        // x exp(x), x ln(x)
+        round_trip(Mul { left: ~x(), right: ~Compose { outer: ~Exp, inner: ~x() } });
+        round_trip(Mul { left: ~x(), right: ~Compose { outer: ~Ln, inner: ~x() } });
+    }
+    
+    #[test]
+    fn unsupported_products_are_none() {
+        // By parts, x ln(x + 1) leaves x^2 / (2 (x + 1)), which is not a supported form.
+        let sum = Plus { left: ~x(), right: ~Constant(Number::int(1)) };
+        assert!(integrate(&Mul { left: ~x(), right: ~Compose { outer: ~Ln, inner: ~sum } }).is_none());
+    }
+}
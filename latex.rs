@@ -0,0 +1,120 @@
+//! LaTeX rendering.
+
+use func;
+use pretty::{Expr, Num, Sym, Neg, Add, Sub, Mul, Div, Pow, Call, Cases};
+
+/// Renders an expression as LaTeX.
+pub fn to_latex(e: &Expr) -> ~str {
+    match *e {
+        Num(ref n) => n.to_str(),
+        Sym(ref s) => symbol(s.as_slice()),
+        Neg(ref a) => format!("-{}", operand(e, &**a, false)),
+        Add(ref a, ref b) => format!("{} + {}", operand(e, &**a, false), operand(e, &**b, true)),
+        Sub(ref a, ref b) => format!("{} - {}", operand(e, &**a, false), operand(e, &**b, true)),
+        Mul(ref a, ref b) => format!("{} \\\\cdot {}", operand(e, &**a, false), operand(e, &**b, true)),
+        
+        // Fractions do not need brackets.
+        Div(ref a, ref b) => format!("\\\\frac\\{{}\\}\\{{}\\}", to_latex(&**a), to_latex(&**b)),
+        
+        // Exponents are grouped, bases are bracketed if not atomic or already raised.
+        Pow(ref a, ref b) => {
+            let base = match **a {
+                Call(ref name, _) if name.as_slice() == "exp" => brackets(to_latex(&**a)),
+                _ => operand(e, &**a, false)
+            };
+            format!("{}^\\{{}\\}", base, to_latex(&**b))
+        },
+        
+        Call(ref name, ref args) => call(name.as_slice(), args.as_slice()),
+        
+        Cases(ref cases, ref default) => {
+            let mut rows: ~[~str] = cases.iter().map(|c| format!("{} & {} {} {}",
+                to_latex(&c.value), to_latex(&c.left), relation(c.relation), to_latex(&c.right)
+            )).collect();
+            for d in default.iter() {
+                rows.push(format!("{} & \\\\text\\{otherwise\\}", to_latex(&**d)));
+            }
+            format!("\\\\begin\\{cases\\} {} \\\\end\\{cases\\}", rows.connect(" \\\\ "))
+        }
+    }
+}
+
+/// Renders an operand, with brackets if needed.
+fn operand(parent: &Expr, e: &Expr, right: bool) -> ~str {
+    if parent.needs_brackets(e, right) {
+        brackets(to_latex(e))
+    } else {
+        to_latex(e)
+    }
+}
+
+/// Surrounds a string with brackets.
+fn brackets(s: ~str) -> ~str {
+    format!("\\\\left({}\\\\right)", s)
+}
+
+/// Renders a symbol, using an upright font for multi-letter names.
+fn symbol(s: &str) -> ~str {
+    if s == "pi" {
+        ~"\\pi"
+    } else if s.char_len() == 1 {
+        s.to_owned()
+    } else {
+        format!("\\\\mathrm\\{{}\\}", s)
+    }
+}
+
+/// Renders a relation.
+fn relation(r: func::Relation) -> &'static str {
+    match r {
+        func::Lt => "<",
+        func::Le => "\\le",
+        func::Gt => ">",
+        func::Ge => "\\ge"
+    }
+}
+
+/// Renders a function call.
+fn call(name: &str, args: &[Expr]) -> ~str {
+    let args: ~[~str] = args.iter().map(|a| to_latex(a)).collect();
+    let last = args[args.len() - 1].clone();
+    
+    match name {
+        "exp"   => format!("e^\\{{}\\}", last),
+        "sqrt"  => format!("\\\\sqrt\\{{}\\}", last),
+        "root"  => format!("\\\\sqrt[{}]\\{{}\\}", args[0], last),
+        "abs"   => format!("\\\\left|{}\\\\right|", last),
+        "log"   => format!("\\\\log_\\{{}\\}{}", args[0], brackets(last)),
+        "log10" => format!("\\\\log_\\{10\\}{}", brackets(last)),
+        "log2"  => format!("\\\\log_\\{2\\}{}", brackets(last)),
+        _ => {
+            let command = match name {
+                "ln" | "sin" | "cos" | "tan" | "cot" | "sinh" | "cosh" | "tanh" =>
+                    format!("\\\\{}", name),
+                "asin" | "acos" | "atan" =>
+                    format!("\\\\arc{}", name.slice_from(1)),
+                "acot" => ~"\\operatorname{arccot}",
+                "asinh" | "acosh" | "atanh" =>
+                    format!("\\\\operatorname\\{ar{}\\}", name.slice_from(1)),
+                "sign" => ~"\\operatorname{sgn}",
+                _ => format!("\\\\operatorname\\{{}\\}", name)
+            };
+            format!("{}{}", command, brackets(args.connect(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_latex;
+    use func::Lt;
+    use pretty::{Num, Sym, Cases, Case};
+    use number::Number;
+    
+    #[test]
+    fn cases_are_separated_by_a_line_break() {
+        let case = Case { left: Sym(~"x"), relation: Lt, right: Num(Number::int(0)), value: Num(Number::int(1)) };
+        let e = Cases(~[case], Some(~Num(Number::int(2))));
+        assert_eq!(to_latex(&e), ~"\\begin{cases} 1 & x < 0 \\\\ 2 & \\text{otherwise} \\end{cases}");
+    }
+}
@@ -0,0 +1,88 @@
+//! Presentation MathML rendering.
+
+use func;
+use pretty::{Expr, Num, Sym, Neg, Add, Sub, Mul, Div, Pow, Call, Cases};
+
+/// Renders an expression as a MathML document fragment.
+pub fn to_mathml(e: &Expr) -> ~str {
+    format!("<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>", element(e))
+}
+
+/// Renders an expression as a MathML element.
+fn element(e: &Expr) -> ~str {
+    match *e {
+        Num(ref n) => format!("<mn>{}</mn>", n.to_str()),
+        Sym(ref s) if s.as_slice() == "pi" => ~"<mi>&#x3C0;</mi>",
+        Sym(ref s) => format!("<mi>{}</mi>", *s),
+        Neg(ref a) => format!("<mrow><mo>-</mo>{}</mrow>", operand(e, &**a, false)),
+        Add(ref a, ref b) => binary(e, &**a, "+", &**b),
+        Sub(ref a, ref b) => binary(e, &**a, "-", &**b),
+        Mul(ref a, ref b) => binary(e, &**a, "&#x22C5;", &**b),
+        
+        // Fractions do not need brackets.
+        Div(ref a, ref b) => format!("<mfrac>{}{}</mfrac>", element(&**a), element(&**b)),
+        
+        // Exponents do not need brackets.
+        Pow(ref a, ref b) => format!("<msup>{}{}</msup>", operand(e, &**a, false), element(&**b)),
+        
+        Call(ref name, ref args) => call(name.as_slice(), args.as_slice()),
+        
+        Cases(ref cases, ref default) => {
+            let mut rows: ~[~str] = cases.iter().map(|c| format!(
+                "<mtr><mtd>{}</mtd><mtd><mrow>{}<mo>{}</mo>{}</mrow></mtd></mtr>",
+                element(&c.value), element(&c.left), relation(c.relation), element(&c.right)
+            )).collect();
+            for d in default.iter() {
+                rows.push(format!("<mtr><mtd>{}</mtd><mtd><mtext>otherwise</mtext></mtd></mtr>", element(&**d)));
+            }
+            format!("<mrow><mo>\\{</mo><mtable>{}</mtable></mrow>", rows.concat())
+        }
+    }
+}
+
+/// Renders a binary operation.
+fn binary(parent: &Expr, left: &Expr, op: &str, right: &Expr) -> ~str {
+    format!("<mrow>{}<mo>{}</mo>{}</mrow>", operand(parent, left, false), op, operand(parent, right, true))
+}
+
+/// Renders an operand, with brackets if needed.
+fn operand(parent: &Expr, e: &Expr, right: bool) -> ~str {
+    if parent.needs_brackets(e, right) {
+        brackets(element(e))
+    } else {
+        element(e)
+    }
+}
+
+/// Surrounds an element with brackets.
+fn brackets(s: ~str) -> ~str {
+    format!("<mrow><mo>(</mo>{}<mo>)</mo></mrow>", s)
+}
+
+/// Renders a relation.
+fn relation(r: func::Relation) -> &'static str {
+    match r {
+        func::Lt => "&lt;",
+        func::Le => "&le;",
+        func::Gt => "&gt;",
+        func::Ge => "&ge;"
+    }
+}
+
+/// Renders a function call.
+fn call(name: &str, args: &[Expr]) -> ~str {
+    let args: ~[~str] = args.iter().map(|a| element(a)).collect();
+    let last = args[args.len() - 1].clone();
+    
+    match name {
+        "exp"   => format!("<msup><mi>e</mi>{}</msup>", last),
+        "sqrt"  => format!("<msqrt>{}</msqrt>", last),
+        "root"  => format!("<mroot>{}{}</mroot>", last, args[0]),
+        "abs"   => format!("<mrow><mo>|</mo>{}<mo>|</mo></mrow>", last),
+        "log"   => format!("<mrow><msub><mi>log</mi>{}</msub>{}</mrow>", args[0], brackets(last)),
+        "log10" => format!("<mrow><msub><mi>log</mi><mn>10</mn></msub>{}</mrow>", brackets(last)),
+        "log2"  => format!("<mrow><msub><mi>log</mi><mn>2</mn></msub>{}</mrow>", brackets(last)),
+        _ => format!("<mrow><mi>{}</mi><mo>&\\#x2061;</mo>{}</mrow>",
+            name, brackets(args.connect("<mo>,</mo>")))
+    }
+}
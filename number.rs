@@ -0,0 +1,307 @@
+//! Numbers.
+//! Integers and fractions are exact rationals, decimal input and irrational results are floats.
+
+use std::cmp;
+use std::num::{FromPrimitive, ToPrimitive, ToStrRadix, Signed, One};
+use std::to_bytes::Cb;
+
+use extra::bigint::BigInt;
+use extra::rational::{Ratio, BigRational};
+
+/// The most bits in the numerator or the denominator of an exact power.
+static MAX_EXACT_BITS: uint = 4096;
+
+/// A number.
+#[deriving(Clone)]
+pub enum Number {
+    Exact(BigRational),
+    Inexact(f64)
+}
+
+impl Number {
+    /// An exact integer.
+    pub fn int(n: int) -> Number {
+        Exact(Ratio::from_integer(big(n)))
+    }
+    
+    /// An exact fraction.
+    pub fn ratio(n: int, d: int) -> Number {
+        Exact(Ratio::new(big(n), big(d)))
+    }
+    
+    /// A floating point number.
+    pub fn float(f: f64) -> Number {
+        Inexact(f)
+    }
+    
+    /// Converts the number to a float.
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Exact(ref r) => big_to_f64(r.numer()) / big_to_f64(r.denom()),
+            Inexact(f) => f
+        }
+    }
+    
+    /// Converts the number to an integer, if it is an exact integer that fits.
+    pub fn to_int(&self) -> Option<int> {
+        match *self {
+            Exact(ref r) if r.is_integer() => r.numer().to_int(),
+            _ => None
+        }
+    }
+    
+    /// Splits a fraction that is not an integer into its numerator and denominator.
+    pub fn to_fraction(&self) -> Option<(Number, Number)> {
+        match *self {
+            Exact(ref r) if !r.is_integer() => Some((
+                Exact(Ratio::from_integer(r.numer().clone())),
+                Exact(Ratio::from_integer(r.denom().clone()))
+            )),
+            _ => None
+        }
+    }
+    
+    /// Checks whether the number is an exact rational.
+    pub fn is_exact(&self) -> bool {
+        match *self {
+            Exact(_) => true,
+            Inexact(_) => false
+        }
+    }
+    
+    /// Checks whether the number is zero.
+    pub fn is_zero(&self) -> bool {
+        self.to_f64() == 0.0
+    }
+    
+    /// Checks whether the number is one.
+    pub fn is_one(&self) -> bool {
+        match *self {
+            Exact(ref r) => *r == One::one(),
+            Inexact(f) => f == 1.0
+        }
+    }
+    
+    /// Checks whether the number is less than zero.
+    pub fn is_negative(&self) -> bool {
+        match *self {
+            Exact(ref r) => r.is_negative(),
+            Inexact(f) => f < 0.0
+        }
+    }
+    
+    /// Checks whether the number is neither infinite nor NaN.
+    pub fn is_finite(&self) -> bool {
+        match *self {
+            Exact(_) => true,
+            Inexact(f) => f.is_finite()
+        }
+    }
+    
+    /// Checks whether the number is an integer.
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            Exact(ref r) => r.is_integer(),
+            Inexact(f) => f == f.floor()
+        }
+    }
+    
+    /// Returns the absolute value.
+    pub fn abs(&self) -> Number {
+        if self.is_negative() { -*self } else { self.clone() }
+    }
+    
+    /// Raises the number to a power, exactly for integer exponents.
+    /// Powers too large to store exactly are floats.
+    pub fn pow(&self, e: &Number) -> Number {
+        match (self, e.to_int()) {
+            (&Exact(ref r), Some(n)) if fits(r, n) && (n >= 0 || !self.is_zero()) => {
+                // Square and multiply.
+                let mut result: BigRational = One::one();
+                let mut square = r.clone();
+                let mut k = n.abs();
+                while k > 0 {
+                    if k % 2 == 1 {
+                        result = result * square;
+                    }
+                    k /= 2;
+                    if k > 0 {
+                        square = square * square;
+                    }
+                }
+                Exact(if n < 0 { result.recip() } else { result })
+            },
+            (&Exact(_), Some(n)) if n < 0 && self.is_zero() => Inexact(Float::nan()),
+            _ => Inexact(self.to_f64().powf(&e.to_f64()))
+        }
+    }
+}
+
+impl Eq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        match (self, other) {
+            (&Exact(ref a), &Exact(ref b)) => a == b,
+            (&Inexact(a), &Inexact(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+impl Ord for Number {
+    fn lt(&self, other: &Number) -> bool {
+        match (self, other) {
+            (&Exact(ref a), &Exact(ref b)) => a < b,
+            _ => self.to_f64() < other.to_f64()
+        }
+    }
+}
+
+impl Add<Number, Number> for Number {
+    fn add(&self, other: &Number) -> Number {
+        match (self, other) {
+            (&Exact(ref a), &Exact(ref b)) => Exact(*a + *b),
+            _ => Inexact(self.to_f64() + other.to_f64())
+        }
+    }
+}
+
+impl Sub<Number, Number> for Number {
+    fn sub(&self, other: &Number) -> Number {
+        match (self, other) {
+            (&Exact(ref a), &Exact(ref b)) => Exact(*a - *b),
+            _ => Inexact(self.to_f64() - other.to_f64())
+        }
+    }
+}
+
+impl Mul<Number, Number> for Number {
+    fn mul(&self, other: &Number) -> Number {
+        match (self, other) {
+            (&Exact(ref a), &Exact(ref b)) => Exact(*a * *b),
+            _ => Inexact(self.to_f64() * other.to_f64())
+        }
+    }
+}
+
+impl Div<Number, Number> for Number {
+    /// Division by an exact zero is undefined, so it gives NaN rather than an infinity.
+    fn div(&self, other: &Number) -> Number {
+        match (self, other) {
+            (&Exact(_), &Exact(_)) if other.is_zero() => Inexact(Float::nan()),
+            (&Exact(ref a), &Exact(ref b)) => Exact(*a / *b),
+            _ => Inexact(self.to_f64() / other.to_f64())
+        }
+    }
+}
+
+impl Neg<Number> for Number {
+    fn neg(&self) -> Number {
+        match *self {
+            Exact(ref r) => Exact(-*r),
+            Inexact(f) => Inexact(-f)
+        }
+    }
+}
+
+impl ToStr for Number {
+    /// Formats the number, without a fractional part if it is an integer.
+    fn to_str(&self) -> ~str {
+        match *self {
+            Exact(ref r) => if r.is_integer() {
+                r.numer().to_str()
+            } else {
+                format!("{}/{}", r.numer().to_str(), r.denom().to_str())
+            },
+            Inexact(f) => if f == f.floor() && f.abs() < 1e15 {
+                format!("{}", f as i64)
+            } else {
+                format!("{}", f)
+            }
+        }
+    }
+}
+
+impl FromStr for Number {
+    /// Integers are exact, decimals are not.
+    fn from_str(s: &str) -> Option<Number> {
+        if s.contains_char('.') || s.contains_char('e') {
+            from_str::<f64>(s).map(|f| Inexact(f))
+        } else {
+            from_str::<BigInt>(s).map(|n| Exact(Ratio::from_integer(n)))
+        }
+    }
+}
+
+impl IterBytes for Number {
+    fn iter_bytes(&self, lsb0: bool, f: Cb) -> bool {
+        match *self {
+            Exact(ref r) => r.numer().iter_bytes(lsb0, |b| f(b)) && r.denom().iter_bytes(lsb0, |b| f(b)),
+            Inexact(x) => x.iter_bytes(lsb0, f)
+        }
+    }
+}
+
+/// Converts an integer to a big integer.
+fn big(n: int) -> BigInt {
+    FromPrimitive::from_int(n).unwrap()
+}
+
+/// Checks whether a rational raised to the n-th power stays within the exact size.
+fn fits(r: &BigRational, n: int) -> bool {
+    let largest = cmp::max(bits(r.numer()), bits(r.denom()));
+    (n.abs() as uint) <= MAX_EXACT_BITS / largest
+}
+
+/// Returns the number of bits in the magnitude of a big integer.
+fn bits(n: &BigInt) -> uint {
+    n.abs().to_str_radix(2).len()
+}
+
+/// Converts a big integer to a float.
+fn big_to_f64(n: &BigInt) -> f64 {
+    from_str::<f64>(n.to_str()).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Number;
+    
+    #[test]
+    fn fractions_stay_exact() {
+        let sum = Number::ratio(1, 2) + Number::ratio(1, 3);
+        assert_eq!(sum, Number::ratio(5, 6));
+        assert_eq!(sum.to_str(), ~"5/6");
+        assert_eq!(Number::ratio(2, 3) * Number::ratio(3, 4), Number::ratio(1, 2));
+    }
+    
+    #[test]
+    fn integer_powers_are_exact() {
+        assert_eq!(Number::int(2).pow(&Number::int(-2)), Number::ratio(1, 4));
+        assert_eq!(Number::ratio(2, 3).pow(&Number::int(3)), Number::ratio(8, 27));
+    }
+    
+    #[test]
+    fn large_powers_are_inexact() {
+        assert!(Number::int(3).pow(&Number::int(2000)).is_exact());
+        assert!(!Number::int(3).pow(&Number::int(5000)).is_exact());
+        assert!(!Number::ratio(1, 3).pow(&Number::int(-5000)).is_exact());
+    }
+    
+    #[test]
+    fn floats_make_results_inexact() {
+        assert_eq!(Number::ratio(1, 2) + Number::float(0.25), Number::float(0.75));
+        assert_eq!(Number::int(2).pow(&Number::ratio(1, 2)), Number::float(2.0f64.powf(&0.5)));
+    }
+    
+    #[test]
+    fn division_by_exact_zero_is_not_finite() {
+        assert!(!(Number::int(1) / Number::int(0)).is_finite());
+        assert!(!Number::int(0).pow(&Number::int(-1)).is_finite());
+    }
+    
+    #[test]
+    fn integers_parse_exactly() {
+        assert_eq!(from_str::<Number>("12"), Some(Number::int(12)));
+        assert_eq!(from_str::<Number>("0.5"), Some(Number::float(0.5)));
+    }
+}
@@ -1,31 +1,44 @@
 //! Function parser.
 
+use std::util;
+use std::ascii::StrAsciiExt;
+
 use func;
+use session;
+use statement;
 use tokenizer;
 use func::{DiffFunc};
 use monad::ResultMonad;
+use number::Number;
+use session::Session;
+use statement::Statement;
 use tokenizer::{Token, Tokenizer};
 
-/// Parses a string into a ~DiffFunc.
-pub struct Parser<R> {
-    priv tokenizer: ~Tokenizer<R>
+/// Parses a string into a ~Statement.
+/// Names defined in the session are expanded while parsing.
+pub struct Parser<'a, R> {
+    priv tokenizer: ~Tokenizer<R>,
+    priv session: &'a Session,
+    
+    /// The name of the implicit argument, which is 'x' except in function definitions.
+    priv argument: ~str
 }
 
-impl<R: Reader> Parser<R> {
+impl<'a, R: Reader> Parser<'a, R> {
     /// Returns a new parser.
-    pub fn new(tokenizer: ~Tokenizer<R>) -> Parser<R> {
-        Parser::<R> { tokenizer: tokenizer }
+    pub fn new(tokenizer: ~Tokenizer<R>, session: &'a Session) -> Parser<'a, R> {
+        Parser { tokenizer: tokenizer, session: session, argument: ~"x" }
     }
     
     /// Creates and runs the parser
-    pub fn parse(reader: R) -> Result<~DiffFunc, ~str> {
+    pub fn parse(reader: R, session: &'a Session) -> Result<~Statement, ~str> {
         let tokenizer = ~Tokenizer::<R>::new(reader);
-        let mut parser = Parser::<R>::new(tokenizer);
+        let mut parser = Parser::new(tokenizer, session);
         parser.run()
     }
     
-    /// Runs the parser and returns an function.
-    pub fn run(&mut self) -> Result<~DiffFunc, ~str> {
+    /// Runs the parser and returns a statement.
+    pub fn run(&mut self) -> Result<~Statement, ~str> {
         match self.statement() {
             Ok(f)  => {
                 // Expect an eof.
@@ -40,9 +53,257 @@ impl<R: Reader> Parser<R> {
     }
     
     /// Parses an statement.
-    fn statement(&mut self) -> Result<~DiffFunc, ~str> {
-        // Just an expression for now.
-        self.expression()
+    fn statement(&mut self) -> Result<~Statement, ~str> {
+        match self.tokenizer.peek() {
+            // Taylor #degree #expression #series_tail
+            Some(tokenizer::Taylor) => {
+                self.tokenizer.take();
+                self.degree().bind(|degree|
+                    self.expression().bind_with(degree, |degree, func| self.series_tail(func, degree))
+                )
+            },
+            
+            // Integrate #expression #integral_tail
+            Some(tokenizer::Integrate) => {
+                self.tokenizer.take();
+                self.expression().bind(|func| self.integral_tail(func))
+            },
+            
+            // Solve #expression #equation_tail #solve_tail
+            Some(tokenizer::Solve) => {
+                self.tokenizer.take();
+                self.expression().bind(|lhs|
+                    self.equation_tail(lhs).bind(|func| self.solve_tail(func))
+                )
+            },
+            
+            // Analyze #expression #bounds
+            Some(tokenizer::Analyze) => {
+                self.tokenizer.take();
+                self.expression().bind(|func|
+                    self.bounds().bind_with(func, |func, (from, to)| Ok(
+                        ~statement::Analyze { func: func, from: from, to: to }
+                    ))
+                )
+            },
+            
+            // #assignment
+            Some(tokenizer::Id(_)) if self.is_assignment() => self.assignment(),
+            
+            // #order #expression #variable #statement_tail
+            _ => {
+                let order = self.order();
+                self.expression().bind(|func|
+                    self.variable().bind_with(func, |func, var| self.statement_tail(func, var, order))
+                )
+            }
+        }
+    }
+    
+    /// Checks whether the next tokens start an assignment, like 'a =' or 'f(t) ='.
+    fn is_assignment(&mut self) -> bool {
+        match self.tokenizer.peek_at(1) {
+            Some(tokenizer::Assign) => true,
+            Some(tokenizer::OpenBracket) => {
+                let param = self.tokenizer.peek_at(2);
+                let close = self.tokenizer.peek_at(3);
+                let assign = self.tokenizer.peek_at(4);
+                match (param, close, assign) {
+                    (Some(tokenizer::Id(_)), Some(tokenizer::CloseBracket), Some(tokenizer::Assign)) => true,
+                    _ => false
+                }
+            },
+            _ => false
+        }
+    }
+    
+    /// Parses an assignment to a variable or a function.
+    fn assignment(&mut self) -> Result<~Statement, ~str> {
+        self.identifier().bind(|name|
+            if is_reserved(name.as_slice()) {
+                Err(format!("Cannot assign to {}.", name))
+            } else {
+                match self.tokenizer.peek() {
+                    // = #expression
+                    Some(tokenizer::Assign) => {
+                        self.tokenizer.take();
+                        self.expression().bind_with(name, |name, value| Ok(
+                            ~statement::Assign { name: name, value: value }
+                        ))
+                    },
+                    
+                    // ( #identifier ) = #expression
+                    _ => self.expect(tokenizer::OpenBracket).bind_with(name, |name, _|
+                        self.identifier().bind_with(name, |name, param|
+                            self.expect(tokenizer::CloseBracket).bind_with((name, param), |(name, param), _|
+                                self.expect(tokenizer::Assign).bind_with((name, param), |(name, param), _|
+                                    self.body(param).bind_with(name, |name, func| Ok(
+                                        ~statement::Define { name: name, func: func }
+                                    ))
+                                )
+                            )
+                        )
+                    )
+                }
+            }
+        )
+    }
+    
+    /// Parses the body of a function definition, with the parameter as the implicit argument.
+    fn body(&mut self, param: ~str) -> Result<~DiffFunc, ~str> {
+        let argument = util::replace(&mut self.argument, param);
+        let body = self.expression();
+        self.argument = argument;
+        body
+    }
+    
+    /// Parses a statement tail.
+    fn statement_tail(&mut self, func: ~DiffFunc, var: ~str, order: uint) -> Result<~Statement, ~str> {
+        match self.tokenizer.peek() {
+            // At #number
+            Some(tokenizer::At) => {
+                self.tokenizer.take();
+                self.number().bind_with((func, var), |(func, var), point| Ok(
+                    ~statement::Evaluate { func: func, var: var, order: order, point: point }
+                ))
+            },
+            
+            // e
+            _ => Ok(~statement::Derive { func: func, var: var, order: order })
+        }
+    }
+    
+    /// Parses a Taylor series statement tail.
+    fn series_tail(&mut self, func: ~DiffFunc, degree: uint) -> Result<~Statement, ~str> {
+        match self.tokenizer.peek() {
+            // At #number
+            Some(tokenizer::At) => {
+                self.tokenizer.take();
+                self.number().bind_with(func, |func, point| Ok(
+                    ~statement::Series { func: func, degree: degree, point: point }
+                ))
+            },
+            
+            // e
+            _ => Ok(~statement::Series { func: func, degree: degree, point: 0.0 })
+        }
+    }
+    
+    /// Parses an integral statement tail.
+    fn integral_tail(&mut self, func: ~DiffFunc) -> Result<~Statement, ~str> {
+        match self.tokenizer.peek() {
+            // #bounds
+            Some(tokenizer::From) => self.bounds().bind_with(func, |func, (from, to)| Ok(
+                ~statement::Quadrature { func: func, from: from, to: to }
+            )),
+            
+            // e
+            _ => Ok(~statement::Integrate { func: func })
+        }
+    }
+    
+    /// Parses the right-hand side of an equation, moving it to the left.
+    fn equation_tail(&mut self, lhs: ~DiffFunc) -> Result<~DiffFunc, ~str> {
+        match self.tokenizer.peek() {
+            // = #expression
+            Some(tokenizer::Assign) => {
+                self.tokenizer.take();
+                self.expression().bind_with(lhs, |lhs, rhs| Ok(~func::Minus { left: lhs, right: rhs }))
+            },
+            
+            // e
+            _ => Ok(lhs)
+        }
+    }
+    
+    /// Parses a solve statement tail.
+    fn solve_tail(&mut self, func: ~DiffFunc) -> Result<~Statement, ~str> {
+        match self.tokenizer.peek() {
+            // Near #number
+            Some(tokenizer::Near) => {
+                self.tokenizer.take();
+                self.number().bind_with(func, |func, guess| Ok(
+                    ~statement::Solve { func: func, guess: guess }
+                ))
+            },
+            
+            // #bounds
+            Some(tokenizer::From) => self.bounds().bind_with(func, |func, (from, to)| Ok(
+                ~statement::SolveBetween { func: func, from: from, to: to }
+            )),
+            
+            Some(t) => Err(format!("Expected near or from, got {}.", t.to_str())),
+            None => Err(~"Expected near or from, got eof.")
+        }
+    }
+    
+    /// Parses the bounds of an interval.
+    fn bounds(&mut self) -> Result<(f64, f64), ~str> {
+        // From #number To #number
+        self.expect(tokenizer::From).bind(|_|
+            self.number().bind(|from|
+                self.expect(tokenizer::To).bind(|_|
+                    self.number().bind(|to| Ok((from, to)))
+                )
+            )
+        )
+    }
+    
+    /// Parses the degree of a series.
+    fn degree(&mut self) -> Result<uint, ~str> {
+        self.number().bind(|f|
+            if f >= 0.0 && f == f.floor() {
+                Ok(f as uint)
+            } else {
+                Err(format!("Expected a non-negative integer degree, got {}.", f))
+            }
+        )
+    }
+    
+    /// Parses the order of the derivative, 1 if not given.
+    /// A name like 'd3' is the order only if it is not defined and an operand follows it.
+    fn order(&mut self) -> uint {
+        let order = match self.tokenizer.peek() {
+            Some(tokenizer::Id(ref s)) if self.session.find(s.as_slice()).is_none() =>
+                derivative_order(s.as_slice()),
+            _ => None
+        };
+        
+        match order {
+            // d<n>
+            Some(n) if self.starts_operand(1) => {
+                self.tokenizer.take();
+                n
+            },
+            
+            // e
+            _ => 1
+        }
+    }
+    
+    /// Checks whether the token n places after the next one can start an operand.
+    fn starts_operand(&mut self, n: uint) -> bool {
+        match self.tokenizer.peek_at(n) {
+            Some(tokenizer::Plus) | Some(tokenizer::Mul) | Some(tokenizer::Div) | Some(tokenizer::Power) |
+            Some(tokenizer::Assign) | Some(tokenizer::CloseBracket) | Some(tokenizer::Comma) |
+            Some(tokenizer::Colon) | Some(tokenizer::Lt) | Some(tokenizer::Le) | Some(tokenizer::Gt) |
+            Some(tokenizer::Ge) | Some(tokenizer::At) | Some(tokenizer::Wrt) | None => false,
+            _ => true
+        }
+    }
+    
+    /// Parses the variable to differentiate by, 'x' if not given.
+    fn variable(&mut self) -> Result<~str, ~str> {
+        match self.tokenizer.peek() {
+            // Wrt #identifier
+            Some(tokenizer::Wrt) => {
+                self.tokenizer.take();
+                self.identifier()
+            },
+            
+            // e
+            _ => Ok(~"x")
+        }
     }
     
     /// Parses an expression.
@@ -120,16 +381,7 @@ impl<R: Reader> Parser<R> {
             Some(tokenizer::Power) => {
                 self.tokenizer.take();
                 self.operand().bind_with(left, |left, right| self.power_ops_tail(
-                    ~func::Compose {
-                        outer: ~func::Exp,
-                        inner: ~func::Mul {
-                            left: ~func::Compose {
-                                outer: ~func::Ln,
-                                inner: left
-                            },
-                            right: right
-                        }
-                    }
+                    ~func::Pow { base: left, exponent: right }
                 ))
             }
             
@@ -138,95 +390,95 @@ impl<R: Reader> Parser<R> {
         }
     }
     
-    /// Parses an operand: a number, an 'x' identifier, a build-in function or an bracketed expression.
+    /// Parses an operand: a number, a variable, a negated operand, a build-in function
+    /// or an bracketed expression.
     fn operand(&mut self) -> Result<~DiffFunc, ~str> {
         match self.tokenizer.peek() {
             // Number
-            Some(tokenizer::Number(f)) => {
+            Some(tokenizer::Number(n)) => {
                 self.tokenizer.take();
-                Ok(~func::Constant(f))
+                Ok(~func::Constant(n))
             },
             
             // Id
             Some(tokenizer::Id(s)) => {
-                if s == ~"x" {
-                    self.tokenizer.take();
-                    Ok(~func::Power(1.0))
-                } else {
-                    Err(format!("Invalid identifier. Use 'x' as the variable name."))
-                }
-            },
-            
-            // Exp #operand
-            Some(tokenizer::Exp) => {
                 self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Exp,
-                        inner: expr
-                    }
-                ))
+                self.name(s)
             },
             
-            // Ln #operand
-            Some(tokenizer::Ln) => {
-                self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Ln,
-                        inner: expr
-                    }
-                ))
-            },
+            // Built-in functions.
+            Some(tokenizer::Exp)  => self.function(func::Exp),
+            Some(tokenizer::Ln)   => self.function(func::Ln),
+            Some(tokenizer::Log10) => self.function(func::Log(Number::int(10))),
+            Some(tokenizer::Log2)  => self.function(func::Log(Number::int(2))),
+            Some(tokenizer::Sqrt)  => self.function(func::Root(Number::int(2))),
+            Some(tokenizer::Sin)  => self.function(func::Sin),
+            Some(tokenizer::Cos)  => self.function(func::Cos),
+            Some(tokenizer::Tg)   => self.function(func::Tan),
+            Some(tokenizer::Ctg)  => self.function(func::Cot),
+            Some(tokenizer::Asin) => self.function(func::Asin),
+            Some(tokenizer::Acos) => self.function(func::Acos),
+            Some(tokenizer::Atan) => self.function(func::Atan),
+            Some(tokenizer::Acot) => self.function(func::Acot),
+            Some(tokenizer::Sinh)  => self.function(func::Sinh),
+            Some(tokenizer::Cosh)  => self.function(func::Cosh),
+            Some(tokenizer::Tanh)  => self.function(func::Tanh),
+            Some(tokenizer::Asinh) => self.function(func::Asinh),
+            Some(tokenizer::Acosh) => self.function(func::Acosh),
+            Some(tokenizer::Atanh) => self.function(func::Atanh),
+            Some(tokenizer::Abs)   => self.function(func::Abs),
+            Some(tokenizer::Sign)  => self.function(func::Sign),
             
-            // Sin #operand
-            Some(tokenizer::Sin) => {
+            // Log ( #expression , #expression )
+            Some(tokenizer::Log) => {
                 self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Sin,
-                        inner: expr
-                    }
-                ))
+                self.arguments().bind(|(base, expr)| match constant(base) {
+                    Ok(ref b) if b.is_negative() || b.is_zero() || b.is_one() =>
+                        Err(format!("Expected a positive logarithm base other than 1, got {}.", b.to_str())),
+                    Ok(b) => Ok(~func::Compose { outer: ~func::Log(b), inner: expr }),
+                    Err(base) => Ok(~func::LogOf { base: base, arg: expr })
+                })
             },
             
-            // Cos #operand
-            Some(tokenizer::Cos) => {
+            // Root ( #expression , #expression )
+            Some(tokenizer::Root) => {
                 self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Cos,
-                        inner: expr
-                    }
-                ))
+                self.arguments().bind(|(degree, expr)| match constant(degree) {
+                    Ok(ref n) if n.is_zero() => Err(~"Expected a non-zero root degree, got 0."),
+                    Ok(n) => Ok(~func::Compose { outer: ~func::Root(n), inner: expr }),
+                    Err(degree) => Ok(~func::RootOf { degree: degree, arg: expr })
+                })
             },
             
-            // Tg #operand
-            Some(tokenizer::Tg) => {
+            // - #power_ops
+            // Negative numbers are read here too, so x-2 is a difference.
+            Some(tokenizer::Minus) => {
                 self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Div {
-                            left: ~func::Sin,
-                            right: ~func::Cos
-                        },
-                        inner: expr
+                self.power_ops().bind(|expr| {
+                    let negated = match *expr {
+                        func::Constant(ref c) => Some(-*c),
+                        _ => None
+                    };
+                    match negated {
+                        Some(c) => Ok(~func::Constant(c)),
+                        None => Ok(~func::Mul {
+                            left: ~func::Constant(Number::int(-1)),
+                            right: expr
+                        })
                     }
-                ))
+                })
             },
             
-            // Ctg #operand
-            Some(tokenizer::Ctg) => {
+            // Piecewise ( #piecewise_cases )
+            Some(tokenizer::Piecewise) => {
                 self.tokenizer.take();
-                self.operand().bind(|expr| Ok(
-                    ~func::Compose {
-                        outer: ~func::Div {
-                            left: ~func::Cos,
-                            right: ~func::Sin
-                        },
-                        inner: expr
-                    }
-                ))
+                self.expect(tokenizer::OpenBracket).bind(|_|
+                    self.piecewise_cases().bind(|func|
+                        self.expect(tokenizer::CloseBracket).bind_with(func, |func, _|
+                            Ok(func)
+                        )
+                    )
+                )
             },
             
             // #bracket_expr
@@ -238,6 +490,124 @@ impl<R: Reader> Parser<R> {
         }
     }
     
+    /// Resolves a name: the argument, a constant, a definition from the session or a free variable.
+    /// Definitions cannot use x or variables that depend on it.
+    fn name(&mut self, s: ~str) -> Result<~DiffFunc, ~str> {
+        if s == self.argument {
+            return Ok(~func::Power(Number::int(1)))
+        }
+        
+        // Inside a definition the argument is the parameter, so there is no x.
+        let in_definition = self.argument != ~"x";
+        if in_definition && s == ~"x" {
+            return Err(format!("Cannot use x in a function of {}.", self.argument))
+        }
+        
+        let definition = self.session.find(s.as_slice()).map(|d| d.clone());
+        match definition {
+            // User functions are applied to a bracketed argument.
+            // #bracket_expr
+            Some(session::Function(body)) => self.bracket_expr().bind_with(body, |body, arg| Ok(
+                ~func::Compose {
+                    outer: ~body,
+                    inner: arg
+                }
+            )),
+            
+            // Stored values are functions of x, which would become the parameter.
+            Some(session::Variable(value)) => if in_definition && value.depends_on_x() {
+                Err(format!("Cannot use {} in a function of {}, because it depends on x.", s, self.argument))
+            } else {
+                Ok(~value)
+            },
+            None => if s == ~"pi" {
+                Ok(~func::Pi)
+            } else if s == ~"e" {
+                Ok(~func::E)
+            } else {
+                Ok(~func::Var(s))
+            }
+        }
+    }
+    
+    /// Parses a built-in function application: the function token followed by an operand.
+    fn function(&mut self, outer: DiffFunc) -> Result<~DiffFunc, ~str> {
+        self.tokenizer.take();
+        self.operand().bind_with(outer, |outer, expr| Ok(
+            ~func::Compose {
+                outer: ~outer,
+                inner: expr
+            }
+        ))
+    }
+    
+    /// Parses a bracketed pair of arguments.
+    fn arguments(&mut self) -> Result<(~DiffFunc, ~DiffFunc), ~str> {
+        self.expect(tokenizer::OpenBracket).bind(|_|
+            self.expression().bind(|first|
+                self.expect(tokenizer::Comma).bind_with(first, |first, _|
+                    self.expression().bind_with(first, |first, second|
+                        self.expect(tokenizer::CloseBracket).bind_with((first, second), |args, _|
+                            Ok(args)
+                        )
+                    )
+                )
+            )
+        )
+    }
+    
+    /// Parses the cases of a piecewise function.
+    fn piecewise_cases(&mut self) -> Result<~DiffFunc, ~str> {
+        self.expression().bind(|left| self.piecewise_case(left))
+    }
+    
+    /// Parses a piecewise case, which is the default value if there is no condition.
+    fn piecewise_case(&mut self, left: ~DiffFunc) -> Result<~DiffFunc, ~str> {
+        match self.relation() {
+            // #relation #expression : #expression #piecewise_rest
+            Some(relation) => self.expression().bind_with(left, |left, right| {
+                let cond = func::Condition { left: left, relation: relation, right: right };
+                self.expect(tokenizer::Colon).bind_with(cond, |cond, _|
+                    self.expression().bind_with(cond, |cond, then|
+                        self.piecewise_rest().bind_with((cond, then), |(cond, then), otherwise| Ok(
+                            ~func::Piecewise { cond: cond, then: then, otherwise: otherwise }
+                        ))
+                    )
+                )
+            }),
+            
+            // e
+            None => Ok(left)
+        }
+    }
+    
+    /// Parses the remaining piecewise cases, the function is undefined if there are none.
+    fn piecewise_rest(&mut self) -> Result<~DiffFunc, ~str> {
+        match self.tokenizer.peek() {
+            // , #piecewise_cases
+            Some(tokenizer::Comma) => {
+                self.tokenizer.take();
+                self.piecewise_cases()
+            },
+            
+            // e
+            _ => Ok(~func::Undefined)
+        }
+    }
+    
+    /// Parses an optional relation.
+    fn relation(&mut self) -> Option<func::Relation> {
+        let relation = match self.tokenizer.peek() {
+            Some(tokenizer::Lt) => func::Lt,
+            Some(tokenizer::Le) => func::Le,
+            Some(tokenizer::Gt) => func::Gt,
+            Some(tokenizer::Ge) => func::Ge,
+            _ => return None
+        };
+        self.tokenizer.take();
+        Some(relation)
+    }
+    
     /// Parses a bracketed expression.
     fn bracket_expr(&mut self) -> Result<~DiffFunc, ~str> {
         self.expect(tokenizer::OpenBracket).bind(|_| 
@@ -249,6 +619,37 @@ impl<R: Reader> Parser<R> {
         )
     }
     
+    /// Parses a number, which may be negative.
+    fn number(&mut self) -> Result<f64, ~str> {
+        match self.tokenizer.peek() {
+            // - #number
+            Some(tokenizer::Minus) => {
+                self.tokenizer.take();
+                self.number().bind(|n| Ok(-n))
+            },
+            
+            // Number
+            Some(tokenizer::Number(n)) => {
+                self.tokenizer.take();
+                Ok(n.to_f64())
+            },
+            Some(t) => Err(format!("Expected a number, got {}.", t.to_str())),
+            None    => Err(~"Expected a number, got eof.")
+        }
+    }
+    
+    /// Parses an identifier.
+    fn identifier(&mut self) -> Result<~str, ~str> {
+        match self.tokenizer.peek() {
+            Some(tokenizer::Id(s)) => {
+                self.tokenizer.take();
+                Ok(s)
+            },
+            Some(t) => Err(format!("Expected an identifier, got {}.", t.to_str())),
+            None    => Err(~"Expected an identifier, got eof.")
+        }
+    }
+    
     /// Consumes a token or fails.
     fn expect(&mut self, token: Token) -> Result<(), ~str> {
         match self.tokenizer.peek() {
@@ -264,3 +665,109 @@ impl<R: Reader> Parser<R> {
         }
     }
 }
+
+/// Reads the order from a derivative prefix like 'd3', in any case.
+fn derivative_order(s: &str) -> Option<uint> {
+    let s = s.to_ascii_lower();
+    if s.len() > 1 && s.starts_with("d") {
+        from_str::<uint>(s.slice_from(1))
+    } else {
+        None
+    }
+}
+
+/// Checks whether a name cannot be assigned to.
+fn is_reserved(name: &str) -> bool {
+    match name {
+        "x" | "pi" | "e" => true,
+        _ => false
+    }
+}
+
+/// Unwraps a constant function, or gives the function back.
+fn constant(f: ~DiffFunc) -> Result<Number, ~DiffFunc> {
+    let c = match *f {
+        func::Constant(ref c) => Some(c.clone()),
+        _ => None
+    };
+    match c {
+        Some(c) => Ok(c),
+        None => Err(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::mem::MemReader;
+    use super::Parser;
+    use func::{Constant, Power};
+    use number::Number;
+    use session::{Session, Variable};
+    use statement::{Statement, Derive, Assign, Solve};
+    
+    fn parse(s: &str) -> Result<~Statement, ~str> {
+        parse_in(s, &Session::new())
+    }
+    
+    fn parse_in(s: &str, session: &Session) -> Result<~Statement, ~str> {
+        Parser::<MemReader>::parse(MemReader::new(s.as_bytes().to_owned()), session)
+    }
+    
+    /// Returns the order of a parsed derivative statement.
+    fn order(s: &str) -> uint {
+        match parse(s) {
+            Ok(~Derive { order: n, .. }) => n,
+            _ => fail!("Expected a derivative: {}", s)
+        }
+    }
+    
+    #[test]
+    fn derivative_prefix_sets_the_order() {
+        assert_eq!(order("d3 sin x"), 3);
+        assert_eq!(order("D2 (x * x)"), 2);
+        assert_eq!(order("sin x"), 1);
+    }
+    
+    #[test]
+    fn derivative_prefix_without_an_operand_is_a_name() {
+        assert_eq!(order("d3"), 1);
+        assert_eq!(order("d3 * x"), 1);
+        assert_eq!(order("x * d3"), 1);
+        assert_eq!(order("d3 wrt d3"), 1);
+    }
+    
+    #[test]
+    fn invalid_log_bases_and_root_degrees_are_errors() {
+        assert!(parse("log(1, x)").is_err());
+        assert!(parse("log(-2, x)").is_err());
+        assert!(parse("root(0, x)").is_err());
+        assert!(parse("log(a, x) + root(n, x)").is_ok());
+    }
+    
+    #[test]
+    fn derivative_prefix_can_be_assigned() {
+        match parse("d3 = x") {
+            Ok(~Assign { name: ref name, .. }) => assert_eq!(name.as_slice(), "d3"),
+            _ => fail!("Expected an assignment")
+        }
+        assert!(parse("x = 2").is_err());
+    }
+
+    #[test]
+    fn definitions_cannot_depend_on_x() {
+        let mut session = Session::new();
+        session.define(~"a", Variable(Constant(Number::int(2))));
+        session.define(~"y", Variable(Power(Number::int(2))));
+        assert!(parse_in("f(t) = t * a", &session).is_ok());
+        assert!(parse_in("f(t) = t * x", &session).is_err());
+        assert!(parse_in("f(t) = t * y", &session).is_err());
+    }
+    
+    #[test]
+    fn minus_reads_negative_numbers_and_differences() {
+        match parse("solve x-2 near -1") {
+            Ok(~Solve { guess: g, .. }) => assert_eq!(g, -1.0),
+            _ => fail!("Expected a solve statement")
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Polynomials in x with dense coefficients.
+
+use std::cmp;
+use std::vec;
+
+use func;
+use func::{DiffFunc, Constant, Power};
+use dag;
+use dag::{Graph, NodeId, Leaf};
+use number::Number;
+
+/// The largest power of x in a polynomial.
+static MAX_POWER: int = 64;
+
+/// The largest power of a sum that is expanded, so (x + 1)^50 stays a power.
+static MAX_EXPANDED_POWER: uint = 4;
+
+/// A polynomial with exact or floating point coefficients.
+/// The coefficient of x^i is at index i, and the last coefficient is not zero.
+#[deriving(Clone, Eq)]
+pub struct Poly {
+    priv coefficients: ~[Number]
+}
+
+impl Poly {
+    /// Constructs a polynomial from its coefficients, lowest degree first.
+    pub fn new(coefficients: ~[Number]) -> Poly {
+        let mut coefficients = coefficients;
+        while coefficients.last_opt().map_or(false, |c| c.is_zero()) {
+            coefficients.pop();
+        }
+        Poly { coefficients: coefficients }
+    }
+    
+    /// A constant polynomial.
+    pub fn constant(c: Number) -> Poly {
+        Poly::new(~[c])
+    }
+    
+    /// The polynomial x^n.
+    pub fn monomial(n: uint) -> Poly {
+        let mut coefficients = vec::from_elem(n + 1, Number::int(0));
+        coefficients[n] = Number::int(1);
+        Poly::new(coefficients)
+    }
+    
+    /// Converts a function to a polynomial, if it is one.
+    pub fn from_func(f: &DiffFunc) -> Option<Poly> {
+        let mut graph = Graph::new();
+        let f = graph.insert(f);
+        Poly::from_node(&graph, f)
+    }
+    
+    /// Converts a node of a graph to a polynomial, if it is one.
+    pub fn from_node(graph: &Graph, id: NodeId) -> Option<Poly> {
+        match graph.node(id) {
+            Leaf(Constant(c)) => Some(Poly::constant(c)),
+            Leaf(Power(ref p)) => exponent(p).map(|n| Poly::monomial(n)),
+            dag::Plus(l, r) =>
+                Poly::from_node(graph, l).and_then(|l| Poly::from_node(graph, r).map(|r| l + r)),
+            dag::Minus(l, r) =>
+                Poly::from_node(graph, l).and_then(|l| Poly::from_node(graph, r).map(|r| l - r)),
+            dag::Mul(l, r) =>
+                Poly::from_node(graph, l).and_then(|l| Poly::from_node(graph, r).map(|r| l * r)),
+            dag::Div(l, r) => match graph.constant(r) {
+                Some(ref c) if !c.is_zero() => Poly::from_node(graph, l).map(|l| l.scale(&(Number::int(1) / *c))),
+                _ => None
+            },
+            dag::Compose(o, g) => match graph.node(o) {
+                Leaf(Power(ref p)) => exponent(p).and_then(|n| Poly::from_node(graph, g).and_then(|g| {
+                    if n <= MAX_EXPANDED_POWER || g.terms() <= 1 { Some(g.pow(n)) } else { None }
+                })),
+                _ => None
+            },
+            _ => None
+        }
+    }
+    
+    /// Converts the polynomial to a function, highest degree first.
+    pub fn to_func(&self) -> DiffFunc {
+        let mut result = None;
+        for k in range(0, self.coefficients.len()).invert() {
+            let c = self.coefficients[k].clone();
+            if c.is_zero() {
+                continue
+            }
+            
+            result = Some(match result {
+                None => term(c, k),
+                Some(sum) => if c.is_negative() {
+                    func::Minus { left: ~sum, right: ~term(-c, k) }
+                } else {
+                    func::Plus { left: ~sum, right: ~term(c, k) }
+                }
+            });
+        }
+        result.unwrap_or(Constant(Number::int(0)))
+    }
+    
+    /// Returns the degree, or None for the zero polynomial.
+    pub fn degree(&self) -> Option<uint> {
+        if self.is_zero() { None } else { Some(self.coefficients.len() - 1) }
+    }
+    
+    /// Checks whether this is the zero polynomial.
+    pub fn is_zero(&self) -> bool {
+        self.coefficients.is_empty()
+    }
+    
+    /// Checks whether every coefficient is exact.
+    pub fn is_exact(&self) -> bool {
+        self.coefficients.iter().all(|c| c.is_exact())
+    }
+    
+    /// Returns the number of non-zero coefficients.
+    fn terms(&self) -> uint {
+        self.coefficients.iter().fold(0u, |n, c| if c.is_zero() { n } else { n + 1 })
+    }
+    
+    /// Returns the coefficient of x^k.
+    pub fn coefficient(&self, k: uint) -> Number {
+        if k < self.coefficients.len() { self.coefficients[k].clone() } else { Number::int(0) }
+    }
+    
+    /// Returns the coefficient of the highest power.
+    pub fn leading(&self) -> Number {
+        self.coefficients.last_opt().map_or(Number::int(0), |c| c.clone())
+    }
+    
+    /// Multiplies by a constant.
+    pub fn scale(&self, c: &Number) -> Poly {
+        Poly::new(self.coefficients.iter().map(|a| *a * *c).collect())
+    }
+    
+    /// Raises the polynomial to a power.
+    pub fn pow(&self, n: uint) -> Poly {
+        let mut result = Poly::constant(Number::int(1));
+        for _ in range(0, n) {
+            result = result * *self;
+        }
+        result
+    }
+    
+    /// Divides by another polynomial, returning the quotient and the remainder.
+    /// Returns None when dividing by the zero polynomial.
+    pub fn div_rem(&self, other: &Poly) -> Option<(Poly, Poly)> {
+        let d = match other.degree() {
+            Some(d) => d,
+            None => return None
+        };
+        
+        let mut remainder = self.coefficients.clone();
+        let mut quotient = vec::from_elem(cmp::max(remainder.len(), d + 1) - d, Number::int(0));
+        while remainder.len() > d {
+            let k = remainder.len() - 1 - d;
+            let c = remainder[remainder.len() - 1] / other.leading();
+            for (i, b) in other.coefficients.iter().enumerate() {
+                remainder[k + i] = remainder[k + i] - c * *b;
+            }
+            quotient[k] = c;
+            // The leading term is cancelled, exactly for exact coefficients.
+            remainder.pop();
+        }
+        
+        Some((Poly::new(quotient), Poly::new(remainder)))
+    }
+    
+    /// Computes the greatest common divisor, with a leading coefficient of one.
+    pub fn gcd(&self, other: &Poly) -> Poly {
+        let mut a = self.clone();
+        let mut b = other.clone();
+        loop {
+            match a.div_rem(&b) {
+                Some((_, r)) => {
+                    a = b;
+                    b = r;
+                },
+                None => break
+            }
+        }
+        
+        if a.is_zero() { a } else { a.scale(&(Number::int(1) / a.leading())) }
+    }
+    
+    /// Computes the derivative.
+    pub fn derivative(&self) -> Poly {
+        Poly::new(self.coefficients.iter().enumerate().skip(1).map(|(k, c)| *c * Number::int(k as int)).collect())
+    }
+    
+    /// Computes the antiderivative with a constant term of zero.
+    pub fn integral(&self) -> Poly {
+        let mut coefficients = ~[Number::int(0)];
+        for (k, c) in self.coefficients.iter().enumerate() {
+            coefficients.push(*c / Number::int(k as int + 1));
+        }
+        Poly::new(coefficients)
+    }
+    
+    /// Evaluates the polynomial at a point.
+    pub fn eval(&self, x: f64) -> f64 {
+        self.coefficients.rev_iter().fold(0.0, |sum, c| sum * x + c.to_f64())
+    }
+}
+
+impl Add<Poly, Poly> for Poly {
+    fn add(&self, other: &Poly) -> Poly {
+        let n = cmp::max(self.coefficients.len(), other.coefficients.len());
+        Poly::new(range(0, n).map(|k| self.coefficient(k) + other.coefficient(k)).collect())
+    }
+}
+
+impl Sub<Poly, Poly> for Poly {
+    fn sub(&self, other: &Poly) -> Poly {
+        let n = cmp::max(self.coefficients.len(), other.coefficients.len());
+        Poly::new(range(0, n).map(|k| self.coefficient(k) - other.coefficient(k)).collect())
+    }
+}
+
+impl Mul<Poly, Poly> for Poly {
+    fn mul(&self, other: &Poly) -> Poly {
+        if self.is_zero() || other.is_zero() {
+            return Poly::new(~[])
+        }
+        
+        let mut coefficients = vec::from_elem(self.coefficients.len() + other.coefficients.len() - 1, Number::int(0));
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other.coefficients.iter().enumerate() {
+                coefficients[i + j] = coefficients[i + j] + *a * *b;
+            }
+        }
+        Poly::new(coefficients)
+    }
+}
+
+impl ToStr for Poly {
+    fn to_str(&self) -> ~str {
+        self.to_func().to_str("x")
+    }
+}
+
+/// Converts a number to an exponent, if it is a non-negative integer small enough to expand.
+fn exponent(p: &Number) -> Option<uint> {
+    match p.to_int() {
+        Some(n) if n >= 0 && n <= MAX_POWER => Some(n as uint),
+        _ => None
+    }
+}
+
+/// Builds the term c * x^k.
+fn term(c: Number, k: uint) -> DiffFunc {
+    let power = match k {
+        0 => return Constant(c),
+        k => Power(Number::int(k as int))
+    };
+    if c.is_one() { power } else { func::Mul { left: ~Constant(c), right: ~power } }
+}
+
+#[cfg(test)]
+mod test {
+    use func;
+    use func::{Constant, Power};
+    use number::Number;
+    use super::Poly;
+    
+    fn poly(coefficients: &[int]) -> Poly {
+        Poly::new(coefficients.iter().map(|&c| Number::int(c)).collect())
+    }
+    
+    #[test]
+    fn div_rem_divides_exactly() {
+        // x^3 - 1 = (x - 1)(x^2 + x + 1)
+        let (q, r) = poly(&[-1, 0, 0, 1]).div_rem(&poly(&[-1, 1])).unwrap();
+        assert_eq!(q, poly(&[1, 1, 1]));
+        assert!(r.is_zero());
+    }
+    
+    #[test]
+    fn div_rem_leaves_a_remainder() {
+        // x^2 + 1 = (x - 1)(x + 1) + 2
+        let (q, r) = poly(&[1, 0, 1]).div_rem(&poly(&[-1, 1])).unwrap();
+        assert_eq!(q, poly(&[1, 1]));
+        assert_eq!(r, poly(&[2]));
+    }
+    
+    #[test]
+    fn division_by_zero_is_none() {
+        assert!(poly(&[1, 1]).div_rem(&poly(&[])).is_none());
+    }
+    
+    #[test]
+    fn gcd_is_monic() {
+        // x^2 - 1 and 2(x + 1)^2
+        assert_eq!(poly(&[-1, 0, 1]).gcd(&poly(&[2, 4, 2])), poly(&[1, 1]));
+        assert_eq!(poly(&[1, 1]).gcd(&poly(&[-1, 1])), poly(&[1]));
+    }
+    
+    #[test]
+    fn only_small_powers_of_sums_are_expanded() {
+        let sum = func::Plus { left: ~Power(Number::int(1)), right: ~Constant(Number::int(1)) };
+        let small = func::Compose { outer: ~Power(Number::int(2)), inner: ~sum.clone() };
+        let large = func::Compose { outer: ~Power(Number::int(50)), inner: ~sum };
+        assert_eq!(Poly::from_func(&small), Some(poly(&[1, 2, 1])));
+        assert!(Poly::from_func(&large).is_none());
+        assert_eq!(Poly::from_func(&Power(Number::int(50))), Some(Poly::monomial(50)));
+    }
+}
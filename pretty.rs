@@ -0,0 +1,287 @@
+//! Precedence-aware pretty printing.
+//! Functions are first lowered to an expression tree with the arguments substituted,
+//! which is then rendered with as few brackets as possible.
+
+use func;
+use func::{DiffFunc, Relation};
+use number::Number;
+use art::to_art;
+use codegen;
+use latex::to_latex;
+use mathml::to_mathml;
+
+/// An output format.
+#[deriving(Eq)]
+pub enum Format {
+    Text,
+    Latex,
+    MathMl,
+    Unicode,
+    Rust,
+    C
+}
+
+/// An expression to be printed.
+#[deriving(Clone, Eq)]
+pub enum Expr {
+    Num(Number),
+    Sym(~str),
+    Neg(~Expr),
+    Add(~Expr, ~Expr),
+    Sub(~Expr, ~Expr),
+    Mul(~Expr, ~Expr),
+    Div(~Expr, ~Expr),
+    Pow(~Expr, ~Expr),
+    Call(~str, ~[Expr]),
+    Cases(~[Case], Option<~Expr>)
+}
+
+/// A case of a piecewise expression.
+#[deriving(Clone, Eq)]
+pub struct Case {
+    left: Expr,
+    relation: Relation,
+    right: Expr,
+    value: Expr
+}
+
+// Precedence levels, higher binds tighter.
+pub static ADD: uint = 1;
+pub static MUL: uint = 2;
+pub static NEG: uint = 3;
+pub static POW: uint = 4;
+pub static ATOM: uint = 5;
+
+impl FromStr for Format {
+    fn from_str(s: &str) -> Option<Format> {
+        match s {
+            "text"  => Some(Text),
+            "latex" => Some(Latex),
+            "mathml" => Some(MathMl),
+            "unicode" => Some(Unicode),
+            "rust" => Some(Rust),
+            "c" => Some(C),
+            _       => None
+        }
+    }
+}
+
+/// Renders a function of 'x' in the given format.
+/// Only code generation can fail.
+pub fn render(f: &DiffFunc, format: Format) -> Result<~str, ~str> {
+    let e = lower(f, &Sym(~"x"));
+    match format {
+        Text  => Ok(e.to_text()),
+        Latex => Ok(to_latex(&e)),
+        MathMl => Ok(to_mathml(&e)),
+        Unicode => Ok(to_art(&e)),
+        Rust => codegen::generate(f, codegen::Rust),
+        C => codegen::generate(f, codegen::C)
+    }
+}
+
+/// Converts a function applied to an argument into an expression.
+pub fn lower(f: &DiffFunc, arg: &Expr) -> Expr {
+    match *f {
+        func::Exp => call("exp", arg),
+        func::Ln  => call("ln", arg),
+        func::Log(ref b) => if *b == Number::int(10) {
+            call("log10", arg)
+        } else if *b == Number::int(2) {
+            call("log2", arg)
+        } else {
+            Call(~"log", ~[Num(b.clone()), arg.clone()])
+        },
+        func::Root(ref n) => if *n == Number::int(2) { call("sqrt", arg) } else { Call(~"root", ~[Num(n.clone()), arg.clone()]) },
+        func::Sin => call("sin", arg),
+        func::Cos => call("cos", arg),
+        func::Tan => call("tan", arg),
+        func::Cot => call("cot", arg),
+        func::Asin => call("asin", arg),
+        func::Acos => call("acos", arg),
+        func::Atan => call("atan", arg),
+        func::Acot => call("acot", arg),
+        func::Sinh => call("sinh", arg),
+        func::Cosh => call("cosh", arg),
+        func::Tanh => call("tanh", arg),
+        func::Asinh => call("asinh", arg),
+        func::Acosh => call("acosh", arg),
+        func::Atanh => call("atanh", arg),
+        func::Abs => call("abs", arg),
+        func::Sign => call("sign", arg),
+        
+        func::Constant(ref c) => if c.is_negative() { Neg(~number(-*c)) } else { number(c.clone()) },
+        func::Pi => Sym(~"pi"),
+        func::E => Sym(~"e"),
+        func::Power(ref p) => power(arg.clone(), p.clone()),
+        func::Var(ref name) => Sym(name.clone()),
+        func::Undefined => Sym(~"undefined"),
+        
+        func::Plus { left: ref l, right: ref r } => add(lower(&**l, arg), lower(&**r, arg)),
+        func::Minus { left: ref l, right: ref r } => sub(lower(&**l, arg), lower(&**r, arg)),
+        func::Mul { left: ref l, right: ref r } => mul(lower(&**l, arg), lower(&**r, arg)),
+        func::Div { left: ref l, right: ref r } => Div(~lower(&**l, arg), ~lower(&**r, arg)),
+        func::Pow { base: ref b, exponent: ref e } => {
+            let base = lower(&**b, arg);
+            let exponent = lower(&**e, arg);
+            match negated(&exponent) {
+                Some(Num(p)) => power(base, -p),
+                Some(e) => Div(~Num(Number::int(1)), ~Pow(~base, ~e)),
+                None => Pow(~base, ~exponent)
+            }
+        },
+        func::LogOf { base: ref b, arg: ref a } => Call(~"log", ~[lower(&**b, arg), lower(&**a, arg)]),
+        func::RootOf { degree: ref n, arg: ref a } => Call(~"root", ~[lower(&**n, arg), lower(&**a, arg)]),
+        func::Compose { outer: ref o, inner: ref i } => lower(&**o, &lower(&**i, arg)),
+        func::Piecewise { cond: _, then: _, otherwise: _ } => {
+            // Collect nested piecewise functions into a single list of cases.
+            let mut cases = ~[];
+            let mut rest = f;
+            loop {
+                match *rest {
+                    func::Piecewise { cond: ref c, then: ref t, otherwise: ref o } => {
+                        cases.push(Case {
+                            left: lower(&*c.left, arg),
+                            relation: c.relation,
+                            right: lower(&*c.right, arg),
+                            value: lower(&**t, arg)
+                        });
+                        rest = &**o;
+                    },
+                    func::Undefined => return Cases(cases, None),
+                    _ => return Cases(cases, Some(~lower(rest, arg)))
+                }
+            }
+        }
+    }
+}
+
+/// Applies a named function.
+fn call(name: &str, arg: &Expr) -> Expr {
+    Call(name.to_owned(), ~[arg.clone()])
+}
+
+/// Converts a number, writing exact fractions as quotients.
+fn number(n: Number) -> Expr {
+    match n.to_fraction() {
+        Some((p, q)) => Div(~Num(p), ~Num(q)),
+        None => Num(n)
+    }
+}
+
+/// Raises to a constant power, using a fraction for negative exponents.
+fn power(base: Expr, p: Number) -> Expr {
+    if p.is_one() {
+        base
+    } else if p.is_negative() {
+        Div(~Num(Number::int(1)), ~power(base, -p))
+    } else {
+        Pow(~base, ~number(p))
+    }
+}
+
+/// Builds a sum, turning negative terms into subtraction.
+fn add(left: Expr, right: Expr) -> Expr {
+    match negated(&right) {
+        Some(right) => Sub(~left, ~right),
+        None => Add(~left, ~right)
+    }
+}
+
+/// Builds a difference, turning negative terms into addition.
+fn sub(left: Expr, right: Expr) -> Expr {
+    match negated(&right) {
+        Some(right) => Add(~left, ~right),
+        None => Sub(~left, ~right)
+    }
+}
+
+/// Builds a product, turning multiplication by -1 into negation.
+fn mul(left: Expr, right: Expr) -> Expr {
+    match left {
+        Neg(~Num(ref n)) if n.is_one() => Neg(~right),
+        left => Mul(~left, ~right)
+    }
+}
+
+/// Returns the expression without its leading minus sign, if it has one.
+fn negated(e: &Expr) -> Option<Expr> {
+    match *e {
+        Neg(ref a) => Some((**a).clone()),
+        Mul(ref a, ref b) => negated(&**a).map(|a| Mul(~a, b.clone())),
+        Div(ref a, ref b) => negated(&**a).map(|a| Div(~a, b.clone())),
+        _ => None
+    }
+}
+
+impl Expr {
+    /// Returns the precedence of the expression.
+    pub fn precedence(&self) -> uint {
+        match *self {
+            Add(_, _) | Sub(_, _) => ADD,
+            Mul(_, _) | Div(_, _) => MUL,
+            Neg(_) => NEG,
+            Pow(_, _) => POW,
+            Num(_) | Sym(_) | Call(_, _) | Cases(_, _) => ATOM
+        }
+    }
+    
+    /// Checks whether an operand of this expression needs brackets.
+    pub fn needs_brackets(&self, operand: &Expr, right: bool) -> bool {
+        self.needs_brackets_for(operand.precedence(), right)
+    }
+    
+    /// Checks whether an operand with the given precedence needs brackets.
+    pub fn needs_brackets_for(&self, o: uint, right: bool) -> bool {
+        let p = self.precedence();
+        
+        match *self {
+            // Brackets for sums and double negation.
+            Neg(_) => o <= ADD || o == NEG,
+            
+            // Brackets for everything that is not an atom.
+            Pow(_, _) => o <= POW,
+            
+            // Subtraction and division are not associative.
+            Sub(_, _) | Div(_, _) if right => o <= p || o == NEG,
+            
+            _ => if right { o < p || o == NEG } else { o < p }
+        }
+    }
+    
+    /// Renders the expression as text.
+    pub fn to_text(&self) -> ~str {
+        match *self {
+            Num(ref n) => n.to_str(),
+            Sym(ref s) => s.clone(),
+            Neg(ref a) => format!("-{}", self.operand_text(&**a, false)),
+            Add(ref a, ref b) => format!("{} + {}", self.operand_text(&**a, false), self.operand_text(&**b, true)),
+            Sub(ref a, ref b) => format!("{} - {}", self.operand_text(&**a, false), self.operand_text(&**b, true)),
+            Mul(ref a, ref b) => format!("{} * {}", self.operand_text(&**a, false), self.operand_text(&**b, true)),
+            Div(ref a, ref b) => format!("{} / {}", self.operand_text(&**a, false), self.operand_text(&**b, true)),
+            Pow(ref a, ref b) => format!("{}^{}", self.operand_text(&**a, false), self.operand_text(&**b, true)),
+            Call(ref name, ref args) => {
+                let args: ~[~str] = args.iter().map(|a| a.to_text()).collect();
+                format!("{}({})", *name, args.connect(", "))
+            },
+            Cases(ref cases, ref default) => {
+                let mut parts: ~[~str] = cases.iter().map(|c| format!("{} {} {}: {}",
+                    c.left.to_text(), c.relation.symbol(), c.right.to_text(), c.value.to_text()
+                )).collect();
+                for d in default.iter() {
+                    parts.push(d.to_text());
+                }
+                format!("piecewise({})", parts.connect(", "))
+            }
+        }
+    }
+    
+    /// Renders an operand as text, with brackets if needed.
+    fn operand_text(&self, operand: &Expr, right: bool) -> ~str {
+        if self.needs_brackets(operand, right) {
+            format!("({})", operand.to_text())
+        } else {
+            operand.to_text()
+        }
+    }
+}
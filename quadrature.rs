@@ -0,0 +1,228 @@
+//! Numerical integration.
+//! Adaptive Gauss-Kronrod quadrature is used first, adaptive Simpson's rule if it
+//! does not reach the tolerance, for example at kinks and singularities.
+//! If neither reaches it, the integral is reported as not converging.
+
+use func::DiffFunc;
+
+/// The requested absolute and relative error.
+static TOLERANCE: f64 = 1e-10;
+
+/// The most subintervals Gauss-Kronrod quadrature splits the interval into.
+static MAX_INTERVALS: uint = 1000;
+
+/// The most function evaluations of Simpson's rule.
+static MAX_EVALUATIONS: uint = 100000;
+
+/// The non-negative Kronrod nodes of the 15-point rule, the last one is the centre.
+static KRONROD_NODES: [f64, ..8] = [
+    0.991455371120812639206854697526329,
+    0.949107912342758524526189684047851,
+    0.864864423359769072789712788640926,
+    0.741531185599394439863864773280788,
+    0.586087235467691130294144845693013,
+    0.405845151377397166906606412076961,
+    0.207784955007898467600689403773245,
+    0.000000000000000000000000000000000
+];
+
+/// The weights of the Kronrod nodes.
+static KRONROD_WEIGHTS: [f64, ..8] = [
+    0.022935322010529224963732008058970,
+    0.063092092629978553290700663189204,
+    0.104790010322250183839876322541518,
+    0.140653259715525918745189590510238,
+    0.169004726639267902826583426598550,
+    0.190350578064785409913256402421014,
+    0.204432940075298892414161999234649,
+    0.209482141084727828012999174891714
+];
+
+/// The weights of the embedded 7-point Gauss rule, which uses every other Kronrod node.
+static GAUSS_WEIGHTS: [f64, ..4] = [
+    0.129484966168869693270611432679082,
+    0.279705391489276667901467771423780,
+    0.381830050505118944950369775488975,
+    0.417959183673469387755102040816327
+];
+
+/// An approximate integral with an estimate of its absolute error.
+pub struct Estimate {
+    value: f64,
+    error: f64
+}
+
+/// Integrates a function of x from a to b.
+pub fn integrate(f: &DiffFunc, a: f64, b: f64) -> Result<Estimate, ~str> {
+    let mut estimate = gauss_kronrod(f, a, b);
+    if !converged(&estimate) {
+        // Simpson's rule also evaluates the end points, so it fails at singularities there.
+        match simpson(f, a, b) {
+            Ok(fallback) => if fallback.error < estimate.error {
+                estimate = fallback;
+            },
+            Err(e) => if !estimate.value.is_finite() {
+                return Err(e)
+            }
+        }
+        
+        // A finite estimate that is still too uncertain is not a result, like for 1/x from 0 to 1.
+        if !converged(&estimate) && estimate.value.is_finite() {
+            return Err(format!("The integral from {} to {} did not converge.", a, b))
+        }
+    }
+    
+    if estimate.value.is_finite() {
+        Ok(estimate)
+    } else {
+        Err(format!("The integral from {} to {} does not exist.", a, b))
+    }
+}
+
+/// Checks whether an estimate is within the tolerance.
+fn converged(e: &Estimate) -> bool {
+    e.error <= TOLERANCE * e.value.abs().max(&1.0)
+}
+
+/// Adaptive Gauss-Kronrod quadrature.
+/// The subinterval with the largest error is halved until the total error is small enough.
+pub fn gauss_kronrod(f: &DiffFunc, a: f64, b: f64) -> Estimate {
+    let mut intervals = ~[(a, b, kronrod_rule(f, a, b))];
+    loop {
+        let total = intervals.iter().fold(Estimate { value: 0.0, error: 0.0 }, |sum, &(_, _, ref e)|
+            Estimate { value: sum.value + e.value, error: sum.error + e.error }
+        );
+        if converged(&total) || intervals.len() >= MAX_INTERVALS || !total.error.is_finite() {
+            return total
+        }
+        
+        // Halve the worst subinterval.
+        let mut worst = 0;
+        for (i, &(_, _, ref e)) in intervals.iter().enumerate() {
+            let (_, _, ref w) = intervals[worst];
+            if e.error > w.error {
+                worst = i;
+            }
+        }
+        let (l, r, _) = intervals.swap_remove(worst);
+        let m = (l + r) / 2.0;
+        intervals.push((l, m, kronrod_rule(f, l, m)));
+        intervals.push((m, r, kronrod_rule(f, m, r)));
+    }
+}
+
+/// Applies the 15-point Kronrod rule, estimating the error by the embedded 7-point Gauss rule.
+fn kronrod_rule(f: &DiffFunc, a: f64, b: f64) -> Estimate {
+    let centre = (a + b) / 2.0;
+    let half = (b - a) / 2.0;
+    
+    let fc = f.eval(centre);
+    let mut kronrod = fc * KRONROD_WEIGHTS[7];
+    let mut gauss = fc * GAUSS_WEIGHTS[3];
+    for j in range(0u, 7) {
+        let dx = half * KRONROD_NODES[j];
+        let pair = f.eval(centre - dx) + f.eval(centre + dx);
+        kronrod += KRONROD_WEIGHTS[j] * pair;
+        if j % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[j / 2] * pair;
+        }
+    }
+    
+    Estimate { value: kronrod * half, error: ((kronrod - gauss) * half).abs() }
+}
+
+/// Adaptive Simpson's rule.
+/// Fails as soon as the function is not finite at a point.
+pub fn simpson(f: &DiffFunc, a: f64, b: f64) -> Result<Estimate, ~str> {
+    let m = (a + b) / 2.0;
+    let (fa, fm, fb) = (f.eval(a), f.eval(m), f.eval(b));
+    match check_finite(&[(a, fa), (m, fm), (b, fb)]) {
+        Some(e) => return Err(e),
+        None => ()
+    }
+    
+    let whole = (b - a) / 6.0 * (fa + 4.0 * fm + fb);
+    let mut evaluations = 3;
+    simpson_step(f, a, b, fa, fm, fb, whole, TOLERANCE, &mut evaluations)
+}
+
+/// Compares Simpson's rule on an interval with the rule on its halves, and recurses if they differ.
+/// Refinement stops once the function has been evaluated too often.
+fn simpson_step(f: &DiffFunc, a: f64, b: f64, fa: f64, fm: f64, fb: f64, whole: f64,
+                tolerance: f64, evaluations: &mut uint) -> Result<Estimate, ~str> {
+    let m = (a + b) / 2.0;
+    let (lm, rm) = ((a + m) / 2.0, (m + b) / 2.0);
+    let (flm, frm) = (f.eval(lm), f.eval(rm));
+    *evaluations += 2;
+    match check_finite(&[(lm, flm), (rm, frm)]) {
+        Some(e) => return Err(e),
+        None => ()
+    }
+    let left = (m - a) / 6.0 * (fa + 4.0 * flm + fm);
+    let right = (b - m) / 6.0 * (fm + 4.0 * frm + fb);
+    
+    // The difference is about 15 times the error of the halves.
+    let delta = left + right - whole;
+    if *evaluations >= MAX_EVALUATIONS || delta.abs() <= 15.0 * tolerance {
+        return Ok(Estimate { value: left + right + delta / 15.0, error: delta.abs() / 15.0 })
+    }
+    
+    let l = match simpson_step(f, a, m, fa, flm, fm, left, tolerance / 2.0, evaluations) {
+        Ok(l) => l,
+        Err(e) => return Err(e)
+    };
+    let r = match simpson_step(f, m, b, fm, frm, fb, right, tolerance / 2.0, evaluations) {
+        Ok(r) => r,
+        Err(e) => return Err(e)
+    };
+    Ok(Estimate { value: l.value + r.value, error: l.error + r.error })
+}
+
+/// Returns an error for the first point where the function is not finite.
+fn check_finite(values: &[(f64, f64)]) -> Option<~str> {
+    values.iter().find(|&&(_, y)| !y.is_finite()).map(|&(x, _)| format!("The function is not finite at {}.", x))
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64;
+    
+    use func::{DiffFunc, Sin, Exp, Abs, Root, Power, Var, Mul};
+    use number::Number;
+    use super::integrate;
+    
+    /// Checks an integral against its exact value.
+    fn check(f: DiffFunc, a: f64, b: f64, exact: f64) {
+        match integrate(&f, a, b) {
+            Ok(e) => assert!((e.value - exact).abs() < 1e-9, "got {} instead of {}", e.value, exact),
+            Err(s) => fail!(s)
+        }
+    }
+    
+    #[test]
+    fn smooth_functions() {
+        check(Power(Number::int(2)), 0.0, 1.0, 1.0 / 3.0);
+        check(Sin, 0.0, f64::consts::PI, 2.0);
+        check(Exp, 0.0, 1.0, f64::consts::E - 1.0);
+    }
+    
+    #[test]
+    fn kinks_and_endpoint_singularities() {
+        check(Abs, -1.0, 1.0, 1.0);
+        check(Root(Number::int(2)), 0.0, 1.0, 2.0 / 3.0);
+    }
+    
+    #[test]
+    fn undefined_functions_fail() {
+        assert!(integrate(&Root(Number::int(2)), -1.0, 1.0).is_err());
+        assert!(integrate(&Mul { left: ~Var(~"a"), right: ~Power(Number::int(1)) }, 0.0, 1.0).is_err());
+    }
+    
+    #[test]
+    fn divergent_integrals_fail() {
+        match integrate(&Power(Number::int(-1)), 0.0, 1.0) {
+            Ok(e) => fail!("Expected an error, got {}.", e.value),
+            Err(s) => assert!(s.contains("did not converge"))
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Rational normalization.
+//! Sums of fractions are combined over a common denominator,
+//! then factors common to the numerator and the denominator are cancelled.
+
+use func::{DiffFunc, Constant, Power};
+use dag::{Graph, NodeId, Node, Leaf, Plus, Minus, Mul, Div, Pow, LogOf, RootOf, Compose, Piecewise};
+use canon::{factors, product};
+use poly::Poly;
+use number::Number;
+
+/// Brings a function into the form numerator / denominator.
+pub fn normalize(f: &DiffFunc) -> DiffFunc {
+    let mut graph = Graph::new();
+    let f = graph.insert(f);
+    let f = graph.normalize(f);
+    graph.extract(f)
+}
+
+/// Brings a node of a graph into the form numerator / denominator.
+/// Operands of other functions are normalized through the graph, which remembers them.
+/// Polynomials with exact coefficients are expanded and divided by their greatest common divisor.
+pub fn normalize_node(graph: &mut Graph, id: NodeId) -> NodeId {
+    let (n, d) = fraction(graph, id);
+    let (n, d) = (graph.canonicalize(n), graph.canonicalize(d));
+    let reduced = match (Poly::from_node(graph, n), Poly::from_node(graph, d)) {
+        (Some(ref p), Some(ref q)) if !q.is_zero() && p.is_exact() && q.is_exact() => reduce(p, q),
+        _ => None
+    };
+    
+    match reduced {
+        Some((p, q)) => match q.degree() {
+            Some(0) => graph.insert(&p.scale(&(Number::int(1) / q.leading())).to_func()),
+            _ => {
+                let quotient = Div(graph.insert(&p.to_func()), graph.insert(&q.to_func()));
+                let quotient = graph.intern(quotient);
+                graph.canonicalize(quotient)
+            }
+        },
+        None => cancel(graph, n, d)
+    }
+}
+
+/// Divides two polynomials by their greatest common divisor.
+fn reduce(p: &Poly, q: &Poly) -> Option<(Poly, Poly)> {
+    let g = p.gcd(q);
+    match (p.div_rem(&g), q.div_rem(&g)) {
+        (Some((p, _)), Some((q, _))) => Some((p, q)),
+        _ => None
+    }
+}
+
+/// Splits a node into a numerator and a denominator.
+fn fraction(graph: &mut Graph, id: NodeId) -> (NodeId, NodeId) {
+    let one = number(graph, Number::int(1));
+    match graph.node(id) {
+        Plus(l, r) => {
+            let (left, right) = (fraction(graph, l), fraction(graph, r));
+            combine(graph, left, right, |a, b| Plus(a, b))
+        },
+        Minus(l, r) => {
+            let (left, right) = (fraction(graph, l), fraction(graph, r));
+            combine(graph, left, right, |a, b| Minus(a, b))
+        },
+        Mul(l, r) => {
+            let (a, b) = fraction(graph, l);
+            let (c, d) = fraction(graph, r);
+            (times(graph, a, c), times(graph, b, d))
+        },
+        Div(l, r) => {
+            let (a, b) = fraction(graph, l);
+            let (c, d) = fraction(graph, r);
+            (times(graph, a, d), times(graph, b, c))
+        },
+        Leaf(Power(ref p)) if p.is_negative() => (one, graph.intern(Leaf(Power(-*p)))),
+        
+        Compose(o, g) => match graph.node(o) {
+            // Integer powers of fractions are fractions.
+            Leaf(Power(ref p)) if p.is_integer() => {
+                let (a, b) = fraction(graph, g);
+                if p.is_negative() {
+                    (power(graph, b, -*p), power(graph, a, -*p))
+                } else {
+                    (power(graph, a, p.clone()), power(graph, b, p.clone()))
+                }
+            },
+            
+            // Everything else is normalized inside.
+            _ => {
+                let inner = graph.normalize(g);
+                (graph.intern(Compose(o, inner)), one)
+            }
+        },
+        Pow(b, e) => {
+            let node = Pow(graph.normalize(b), graph.normalize(e));
+            (graph.intern(node), one)
+        },
+        LogOf(b, a) => {
+            let node = LogOf(graph.normalize(b), graph.normalize(a));
+            (graph.intern(node), one)
+        },
+        RootOf(n, a) => {
+            let node = RootOf(graph.normalize(n), graph.normalize(a));
+            (graph.intern(node), one)
+        },
+        Piecewise(l, relation, r, t, o) => {
+            let node = Piecewise(
+                graph.normalize(l),
+                relation,
+                graph.normalize(r),
+                graph.normalize(t),
+                graph.normalize(o)
+            );
+            (graph.intern(node), one)
+        },
+        _ => (id, one)
+    }
+}
+
+/// Adds or subtracts two fractions, using the product of the denominators unless they are equal.
+fn combine(graph: &mut Graph, left: (NodeId, NodeId), right: (NodeId, NodeId), op: |NodeId, NodeId| -> Node)
+        -> (NodeId, NodeId) {
+    let ((a, b), (c, d)) = (left, right);
+    if b == d {
+        (graph.intern(op(a, c)), b)
+    } else {
+        let (ad, cb) = (times(graph, a, d), times(graph, c, b));
+        (graph.intern(op(ad, cb)), times(graph, b, d))
+    }
+}
+
+/// Multiplies two nodes, leaving out factors of one.
+fn times(graph: &mut Graph, a: NodeId, b: NodeId) -> NodeId {
+    if graph.is_one(a) {
+        b
+    } else if graph.is_one(b) {
+        a
+    } else {
+        graph.intern(Mul(a, b))
+    }
+}
+
+/// Raises a node to a positive power.
+fn power(graph: &mut Graph, a: NodeId, p: Number) -> NodeId {
+    if graph.is_one(a) {
+        a
+    } else {
+        let power = graph.intern(Leaf(Power(p)));
+        graph.intern(Compose(power, a))
+    }
+}
+
+/// Adds a constant.
+fn number(graph: &mut Graph, c: Number) -> NodeId {
+    graph.intern(Leaf(Constant(c)))
+}
+
+/// Returns the terms of a sum.
+pub fn terms(graph: &mut Graph, id: NodeId) -> ~[NodeId] {
+    match graph.node(id) {
+        Plus(l, r) => {
+            let mut result = terms(graph, l);
+            result.push(r);
+            result
+        },
+        Minus(l, r) => {
+            let mut result = terms(graph, l);
+            let minus_one = number(graph, Number::int(-1));
+            result.push(graph.intern(Mul(minus_one, r)));
+            result
+        },
+        _ => ~[id]
+    }
+}
+
+/// Divides the numerator and the denominator by the factors they have in common.
+/// A factor is common if every term of the numerator has it.
+fn cancel(graph: &mut Graph, n: NodeId, d: NodeId) -> NodeId {
+    let mut parts: ~[(Number, ~[(NodeId, Number)])] = terms(graph, n).iter().map(|&t| factors(graph, t)).collect();
+    let (d_coefficient, mut d_factors) = factors(graph, d);
+    
+    let bases: ~[NodeId] = d_factors.iter().map(|&(b, _)| b).collect();
+    for &base in bases.iter() {
+        // The smallest exponent of the factor in the denominator and the terms of the numerator.
+        let common = parts.iter().fold(exponent_of(d_factors.as_slice(), base), |m, &(_, ref fs)| {
+            let e = exponent_of(fs.as_slice(), base);
+            if e < m { e } else { m }
+        });
+        
+        if !common.is_negative() && !common.is_zero() {
+            d_factors = divide(d_factors, base, &common);
+            parts = parts.move_iter().map(|(c, fs)| (c, divide(fs, base, &common))).collect();
+        }
+    }
+    
+    let mut numerator = None;
+    for (c, fs) in parts.move_iter() {
+        let term = product(graph, c, fs);
+        numerator = Some(match numerator {
+            None => term,
+            Some(n) => graph.intern(Plus(n, term))
+        });
+    }
+    let numerator = match numerator {
+        Some(n) => n,
+        None => number(graph, Number::int(0))
+    };
+    let denominator = product(graph, d_coefficient, d_factors);
+    
+    let quotient = graph.intern(Div(numerator, denominator));
+    graph.canonicalize(quotient)
+}
+
+/// Returns the exponent of a factor, or 0 if there is no such factor.
+fn exponent_of(fs: &[(NodeId, Number)], base: NodeId) -> Number {
+    fs.iter().find(|&&(b, _)| b == base).map_or(Number::int(0), |&(_, ref e)| e.clone())
+}
+
+/// Divides a product by a factor raised to an exponent.
+fn divide(fs: ~[(NodeId, Number)], base: NodeId, exponent: &Number) -> ~[(NodeId, Number)] {
+    fs.move_iter().map(|(b, e)| if b == base { (b, e - *exponent) } else { (b, e) }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use func::{Constant, Power, Plus, Minus, Div};
+    use number::Number;
+    use super::normalize;
+    
+    #[test]
+    fn common_factors_cancel() {
+        // (x^2 + x) / x = x + 1
+        let f = Div {
+            left: ~Plus { left: ~Power(Number::int(2)), right: ~Power(Number::int(1)) },
+            right: ~Power(Number::int(1))
+        };
+        assert_eq!(normalize(&f), normalize(&Plus { left: ~Power(Number::int(1)), right: ~Constant(Number::int(1)) }));
+    }
+    
+    #[test]
+    fn fractions_are_combined() {
+        // 1/x + 1/x = 2/x
+        let one_over_x = Div { left: ~Constant(Number::int(1)), right: ~Power(Number::int(1)) };
+        let f = Plus { left: ~one_over_x.clone(), right: ~one_over_x };
+        assert_eq!(normalize(&f), normalize(&Div { left: ~Constant(Number::int(2)), right: ~Power(Number::int(1)) }));
+    }
+    
+    #[test]
+    fn polynomials_are_divided_by_their_gcd() {
+        // (x^2 - 1) / (x - 1) = x + 1
+        let f = Div {
+            left: ~Minus { left: ~Power(Number::int(2)), right: ~Constant(Number::int(1)) },
+            right: ~Minus { left: ~Power(Number::int(1)), right: ~Constant(Number::int(1)) }
+        };
+        assert_eq!(normalize(&f), normalize(&Plus { left: ~Power(Number::int(1)), right: ~Constant(Number::int(1)) }));
+    }
+}
@@ -0,0 +1,73 @@
+//! Taylor series expansion.
+
+use func::{DiffFunc, Constant, Power, Plus, Minus, Mul, Compose};
+use dag::Graph;
+use simplify::{Simplify, simplify_node};
+use number::Number;
+
+/// Computes the Taylor polynomial of a function of the given degree around a point.
+/// Coefficients are exact where the derivatives have rational values at the point.
+pub fn taylor(f: &DiffFunc, a: f64, degree: uint) -> Result<DiffFunc, ~str> {
+    let point = if a == a.floor() && a.abs() < 1e9 { Number::int(a as int) } else { Number::float(a) };
+    
+    // Powers of (x - a), or just x for a Maclaurin series.
+    let shift = if a == 0.0 {
+        Power(Number::int(1))
+    } else {
+        Minus { left: ~Power(Number::int(1)), right: ~Constant(point.clone()) }
+    };
+    
+    // The derivatives share their subfunctions in the graph.
+    let mut graph = Graph::new();
+    let f = graph.insert(f);
+    let mut df = simplify_node(&mut graph, f);
+    let mut poly = Constant(Number::int(0));
+    let mut factorial = Number::int(1);
+    
+    for k in range(0, degree + 1) {
+        if k > 0 {
+            let d = graph.partial(df, "x");
+            df = simplify_node(&mut graph, d);
+            factorial = factorial * Number::int(k as int);
+        }
+        
+        let value = match graph.eval_exact(df, &point) {
+            Some(v) => v,
+            None => Number::float(graph.eval(df, a))
+        };
+        let coefficient = value / factorial;
+        if !coefficient.is_finite() {
+            return Err(format!("The derivative of order {} is not defined at {}.", k, a))
+        }
+        
+        if k == 0 {
+            poly = Constant(coefficient);
+        } else {
+            poly = Plus {
+                left: ~poly,
+                right: ~Mul {
+                    left: ~Constant(coefficient),
+                    right: ~Compose { outer: ~Power(Number::int(k as int)), inner: ~shift.clone() }
+                }
+            };
+        }
+    }
+    
+    Ok(poly.simplify())
+}
+
+#[cfg(test)]
+mod test {
+    use func::Exp;
+    use number::Number;
+    use poly::Poly;
+    use super::taylor;
+    
+    #[test]
+    fn rational_coefficients_are_exact() {
+        // exp(x) = 1 + x + x^2/2 + x^3/6 + ...
+        let p = Poly::from_func(&taylor(&Exp, 0.0, 5).unwrap()).unwrap();
+        assert_eq!(p.coefficient(3), Number::ratio(1, 6));
+        assert_eq!(p.coefficient(5), Number::ratio(1, 120));
+    }
+}
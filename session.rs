@@ -0,0 +1,37 @@
+//! Definitions made during a calculator session.
+
+use std::hashmap::HashMap;
+
+use func::DiffFunc;
+
+/// A user-defined name.
+#[deriving(Clone)]
+pub enum Definition {
+    /// A function of its argument, applied with brackets like 'f(sin(x))'.
+    Function(DiffFunc),
+    
+    /// A value, which may depend on 'x'.
+    Variable(DiffFunc)
+}
+
+/// The functions and variables defined so far.
+pub struct Session {
+    priv definitions: HashMap<~str, Definition>
+}
+
+impl Session {
+    /// Constructs a session without definitions.
+    pub fn new() -> Session {
+        Session { definitions: HashMap::new() }
+    }
+    
+    /// Defines a name, replacing an earlier definition.
+    pub fn define(&mut self, name: ~str, definition: Definition) {
+        self.definitions.insert(name, definition);
+    }
+    
+    /// Looks up a name.
+    pub fn find<'a>(&'a self, name: &str) -> Option<&'a Definition> {
+        self.definitions.find_equiv(&name)
+    }
+}
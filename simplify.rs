@@ -1,128 +1,539 @@
 //! Module used to simplify functions.
 //! Pretty hacky.
 
-use func::{DiffFunc, Exp, Ln, Sin, Cos, Constant, Power, Plus, Minus, Mul, Div, Compose};
+use func::{DiffFunc, Exp, Ln, Log, Root, Sin, Cos, Tan, Cot, Asin, Acos, Atan, Acot};
+use func::{Sinh, Cosh, Tanh, Asinh, Acosh, Atanh, Abs};
+use func::{Constant, Pi, E, Power, Undefined};
+use dag::{Graph, NodeId, Leaf, Plus, Minus, Mul, Div, Pow, LogOf, RootOf, Compose, Piecewise};
+use canon::power_of_power;
+use poly::Poly;
+use number::Number;
+
+/// Computes the n-th partial derivative, simplifying between steps.
+/// The derivatives are built in a graph, so shared subfunctions are not copied.
+pub fn derivative_n(f: &DiffFunc, var: &str, n: uint) -> DiffFunc {
+    let mut graph = Graph::new();
+    let f = graph.insert(f);
+    
+    // Polynomials in x are differentiated directly.
+    if var == "x" {
+        let normalized = graph.normalize(f);
+        match Poly::from_node(&graph, normalized) {
+            Some(p) => return range(0, n).fold(p, |p, _| p.derivative()).to_func(),
+            None => ()
+        }
+    }
+    
+    let mut f = simplify_node(&mut graph, f);
+    for _ in range(0, n) {
+        let df = graph.partial(f, var);
+        f = simplify_node(&mut graph, df);
+    }
+    graph.extract(f)
+}
+
+/// The most times rewriting and normalization alternate.
+static MAX_PASSES: uint = 20;
+
+/// Simplifies a node of a graph.
+/// Rewriting and normalization alternate until the node stops changing.
+pub fn simplify_node(graph: &mut Graph, f: NodeId) -> NodeId {
+    let mut f = f;
+    for _ in range(0, MAX_PASSES) {
+        let rewritten = graph.simplify(f);
+        let normalized = graph.normalize(rewritten);
+        if normalized == f {
+            break
+        }
+        f = normalized;
+    }
+    f
+}
 
 /// Simplifies something.
 pub trait Simplify {
     fn simplify(&self) -> Self;
-    fn simplify_step(&self) -> Self;
 }
 
 impl Simplify for DiffFunc {
     /// Constructs a new, simplified function.
     fn simplify(&self) -> DiffFunc {
-        let mut old = self.clone();
-        let mut new = old.simplify_step();
-        while old != new {
-            old = new;
-            new = old.simplify_step();
+        let mut graph = Graph::new();
+        let f = graph.insert(self);
+        let f = simplify_node(&mut graph, f);
+        graph.extract(f)
+    }
+}
+
+/// Rewrites a node whose operands are already simplified.
+/// Only the operand nodes are inspected, and operands are compared by index.
+/// Returns the node itself if no rule applies.
+pub fn rewrite_node(graph: &mut Graph, id: NodeId) -> NodeId {
+    match identity(graph, id) {
+        Some(g) => return g,
+        None => ()
+    }
+    match special_angle(graph, id) {
+        Some(g) => return g,
+        None => ()
+    }
+    
+    let result = match graph.node(id) {
+        Plus(l, r) => rewrite_plus(graph, l, r),
+        Minus(l, r) => rewrite_minus(graph, l, r),
+        Mul(l, r) => rewrite_mul(graph, l, r),
+        Div(l, r) => rewrite_div(graph, l, r),
+        Pow(b, e) => rewrite_pow(graph, b, e),
+        
+        // A constant base or degree is folded into the built-in function.
+        LogOf(b, a) => match graph.constant(b) {
+            Some(c) => Some(apply(graph, Log(c), a)),
+            None => None
+        },
+        RootOf(n, a) => match graph.constant(n) {
+            Some(c) => Some(apply(graph, Root(c), a)),
+            None => None
+        },
+        
+        Compose(o, i) => rewrite_compose(graph, o, i),
+        Piecewise(_, _, _, t, o) if t == o => Some(t),
+        _ => None
+    };
+    result.unwrap_or(id)
+}
+
+/// Rewrites l + r.
+fn rewrite_plus(graph: &mut Graph, l: NodeId, r: NodeId) -> Option<NodeId> {
+    match (graph.constant(l), graph.constant(r)) {
+        (Some(x), Some(y)) => return Some(number(graph, x + y)),
+        _ => ()
+    }
+    match graph.node(r) {
+        Plus(f2, f3) => {
+            let left = graph.intern(Plus(l, f2));
+            return Some(graph.intern(Plus(left, f3)))
+        },
+        _ => ()
+    }
+    
+    // sin(f)^2 + cos(f)^2 = 1, with the other square anywhere in the sum
+    let rest = match pythagorean_partner(graph, r) {
+        Some(partner) => remove_term(graph, l, partner),
+        None => None
+    };
+    match rest {
+        Some(rest) => {
+            let one = number(graph, Number::int(1));
+            Some(graph.intern(Plus(rest, one)))
+        },
+        None => if l == r {
+            let two = number(graph, Number::int(2));
+            Some(graph.intern(Mul(two, l)))
+        } else {
+            None
         }
-        new
+    }
+}
+
+/// Rewrites l - r.
+fn rewrite_minus(graph: &mut Graph, l: NodeId, r: NodeId) -> Option<NodeId> {
+    match (graph.constant(l), graph.constant(r)) {
+        (Some(x), Some(y)) => return Some(number(graph, x - y)),
+        _ => ()
     }
     
-    /// Constructs a new, simplified function.
-    fn simplify_step(&self) -> DiffFunc {
-        match *self {
-            Exp => Exp,
-            Ln  => Ln,
-            Sin => Sin,
-            Cos => Cos,
-            
-            Constant(f) => Constant(f),
-            Power(f)    => if f == 0.0 { Constant(1.0) } else { Power(f) },
-            
-            Plus { left: ref l, right: ref r } => {
-                let l = l.simplify();
-                let r = r.simplify();
-                
-                match (l, r) {
-                    (Constant(x), Constant(y)) => Constant(x + y),
-                    (Constant(0.0), r) => r,
-                    (l, Constant(0.0)) => l,
-                    (f1, Plus { left: f2, right: f3 }) => Plus { left: ~Plus { left: ~f1, right: f2 }, right: f3 },
-                    (l, r) => if l == r { 
-                        Mul { left: ~Constant(2.0), right: ~l } 
-                    } else { 
-                        Plus { left: ~l, right: ~r } 
-                    }
-                }
-            },
-            
-            Minus { left: ref l, right: ref r } => {
-                let l = l.simplify();
-                let r = r.simplify();
-                
-                match (l, r) {
-                    (Constant(x), Constant(y)) => Constant(x - y),
-                    (Constant(0.0), r) => Mul { left: ~Constant(-1.0), right: ~r },
-                    (l, Constant(0.0)) => l,
-                    (l, r) => Minus { left: ~l, right: ~r }
-                }
-            },
-            
-            Mul { left: ref l, right: ref r } => {
-                let l = l.simplify();
-                let r = r.simplify();
-                
-                match (l, r) {
-                    (Constant(x), Constant(y)) => Constant(x * y),
-                    (Constant(1.0), r) => r,
-                    (l, Constant(1.0)) => l,
-                    (Constant(0.0), _) => Constant(0.0),
-                    (_, Constant(0.0)) => Constant(0.0),
-                    (Power(a), Power(b)) => Power(a + b),
-                    (f, Mul { left: ~Power(a), right: ~Power(b) }) => Mul { left: ~Power(a + b), right: ~f },
-                    (Power(a), Mul { left: f, right: ~Power(b) }) => Mul { left: ~Power(a + b), right: f },
-                    (Power(a), Mul { left: ~Power(b), right: f }) => Mul { left: ~Power(a + b), right: f },
-                    (Mul { left: ~Power(a), right: ~Power(b) }, f) => Mul { left: ~Power(a + b), right: ~f },
-                    (Mul { left: ~Power(a), right: f }, Power(b)) => Mul { left: ~Power(a + b), right: f },
-                    (Mul { left: f, right: ~Power(a) }, Power(b)) => Mul { left: ~Power(a + b), right: f },
-                    (Compose { outer: ~Exp, inner: f1 }, Compose { outer: ~Exp, inner: f2 }) => 
-                        Compose { outer: ~Exp, inner: ~Plus { left: f1, right: f2 } },
-                    (f1, Mul { left: f2, right: f3 }) => Mul { left: ~Mul { left: ~f1, right: f2 }, right: f3 },
-                    (l, r) => Mul { left: ~l, right: ~r }
-                }
-            },
-            
-            Div { left: ref l, right: ref r } => {
-                let l = l.simplify();
-                let r = r.simplify();
-                
-                match (l, r) {
-                    (Constant(x), Constant(y)) => Constant(x / y),
-                    (l, Constant(1.0)) => l,
-                    (l, r) => Div { left: ~l, right: ~r }
-                }
+    match (square_of(graph, l), square_of(graph, r)) {
+        // cosh(f)^2 - sinh(f)^2 = 1
+        (Some((Cosh, f1)), Some((Sinh, f2))) if f1 == f2 => Some(number(graph, Number::int(1))),
+        
+        // sinh(f)^2 - cosh(f)^2 = -1
+        (Some((Sinh, f1)), Some((Cosh, f2))) if f1 == f2 => Some(number(graph, Number::int(-1))),
+        
+        // cos(f)^2 - sin(f)^2 = cos(2f)
+        (Some((Cos, f1)), Some((Sin, f2))) if f1 == f2 => {
+            let two = number(graph, Number::int(2));
+            let double = graph.intern(Mul(two, f1));
+            Some(apply(graph, Cos, double))
+        },
+        
+        _ => None
+    }
+}
+
+/// Rewrites l * r.
+fn rewrite_mul(graph: &mut Graph, l: NodeId, r: NodeId) -> Option<NodeId> {
+    match (graph.constant(l), graph.constant(r)) {
+        (Some(x), Some(y)) => return Some(number(graph, x * y)),
+        _ => ()
+    }
+    
+    // x^a * x^b = x^(a + b), also when one of them has another factor
+    match (power_of(graph, l), power_of(graph, r)) {
+        (Some(a), Some(b)) => return Some(graph.intern(Leaf(Power(a + b)))),
+        (Some(a), None) => match split_power(graph, r) {
+            Some((b, f)) => return Some(times_power(graph, a + b, f)),
+            None => ()
+        },
+        (None, Some(b)) => match split_power(graph, l) {
+            Some((a, f)) => return Some(times_power(graph, a + b, f)),
+            None => ()
+        },
+        (None, None) => ()
+    }
+    
+    match (applied(graph, l), applied(graph, r)) {
+        (Some((Exp, f1)), Some((Exp, f2))) => {
+            let sum = graph.intern(Plus(f1, f2));
+            return Some(apply(graph, Exp, sum))
+        },
+        _ => ()
+    }
+    
+    // 2 * sin(f) * cos(f) = sin(2f)
+    match graph.node(l) {
+        Mul(c, g) if graph.constant(c) == Some(Number::int(2)) => match (applied(graph, g), applied(graph, r)) {
+            (Some((Cos, f1)), Some((Sin, f2))) | (Some((Sin, f1)), Some((Cos, f2))) if f1 == f2 => {
+                let double = graph.intern(Mul(c, f1));
+                return Some(apply(graph, Sin, double))
             },
-            
-            Compose { outer: ref o, inner: ref i } => {
-                let o = o.simplify();
-                let i = i.simplify();
-                
-                match (o, i) {
-                    (Power(a), Power(b)) => Power(a * b),
-                    (Exp, Ln) => Power(1.0),
-                    (Exp, Compose { outer: ~Ln, inner: f }) => *f,
-                    (Exp, Mul {
-                        left: ~Compose {
-                            outer: ~Ln,
-                            inner: f1
-                        },
-                        right: ~Constant(c)
-                    }) => Compose { outer: ~Power(c), inner: f1 },
-                    (Exp, Mul {
-                        left: ~Constant(c),
-                        right: ~Compose {
-                            outer: ~Ln,
-                            inner: f1
-                        }
-                    }) => Compose { outer: ~Power(c), inner: f1 },
-                    (Ln, Exp) => Power(1.0),
-                    (Ln, Compose { outer: ~Exp, inner: f }) => *f,
-                    (o, i) => Compose { outer: ~o, inner: ~i }
-                }
+            _ => ()
+        },
+        _ => ()
+    }
+    
+    match graph.node(r) {
+        Mul(f2, f3) => {
+            let left = graph.intern(Mul(l, f2));
+            Some(graph.intern(Mul(left, f3)))
+        },
+        _ => None
+    }
+}
+
+/// Rewrites l / r.
+fn rewrite_div(graph: &mut Graph, l: NodeId, r: NodeId) -> Option<NodeId> {
+    match (graph.constant(l), graph.constant(r)) {
+        (Some(_), Some(ref y)) if y.is_zero() => return Some(graph.intern(Leaf(Undefined))),
+        (Some(x), Some(y)) => return Some(number(graph, x / y)),
+        _ => ()
+    }
+    
+    let (quotient, f) = match (applied(graph, l), applied(graph, r)) {
+        // sinh(f) / cosh(f) = tanh(f)
+        (Some((Sinh, f1)), Some((Cosh, f2))) if f1 == f2 => (Tanh, f1),
+        
+        // sin(f) / cos(f) = tan(f)
+        (Some((Sin, f1)), Some((Cos, f2))) if f1 == f2 => (Tan, f1),
+        
+        // cos(f) / sin(f) = cot(f)
+        (Some((Cos, f1)), Some((Sin, f2))) if f1 == f2 => (Cot, f1),
+        
+        _ => return None
+    };
+    Some(apply(graph, quotient, f))
+}
+
+/// Rewrites b^e.
+fn rewrite_pow(graph: &mut Graph, b: NodeId, e: NodeId) -> Option<NodeId> {
+    let result = match (graph.node(b), graph.constant(e)) {
+        (Leaf(Constant(x)), Some(y)) => Leaf(Constant(x.pow(&y))),
+        (Leaf(Power(a)), Some(c)) => {
+            let x = graph.intern(Leaf(Power(Number::int(1))));
+            return Some(power_of_power(graph, x, &a, &c))
+        },
+        (_, Some(c)) => {
+            let power = graph.intern(Leaf(Power(c)));
+            Compose(power, b)
+        },
+        (Leaf(E), None) => {
+            let exp = graph.intern(Leaf(Exp));
+            Compose(exp, e)
+        },
+        _ => return None
+    };
+    Some(graph.intern(result))
+}
+
+/// Rewrites o(i) for a built-in function o.
+fn rewrite_compose(graph: &mut Graph, o: NodeId, i: NodeId) -> Option<NodeId> {
+    let outer = match leaf(graph, o) {
+        Some(f) => f,
+        None => return None
+    };
+    
+    match (outer.clone(), graph.node(i)) {
+        (Power(a), Leaf(Power(b))) => {
+            let x = graph.intern(Leaf(Power(Number::int(1))));
+            return Some(power_of_power(graph, x, &b, &a))
+        },
+        
+        // Roots of even powers of x are powers of |x|.
+        (Root(ref n), Leaf(Power(ref b))) if (*b / Number::int(2)).is_integer() => {
+            let x = graph.intern(Leaf(Power(Number::int(1))));
+            return Some(power_of_power(graph, x, b, &(Number::int(1) / *n)))
+        },
+        (Exp, Leaf(Ln)) | (Ln, Leaf(Exp)) => return Some(graph.intern(Leaf(Power(Number::int(1))))),
+        (Ln, Leaf(E)) => return Some(number(graph, Number::int(1))),
+        (Abs, Leaf(Constant(c))) => return Some(number(graph, c.abs())),
+        _ => ()
+    }
+    
+    // Inverse functions cancel, like exp(ln(f)) = f.
+    match (outer.clone(), applied(graph, i)) {
+        (Exp, Some((Ln, f))) | (Ln, Some((Exp, f))) => return Some(f),
+        (Ln, Some((Power(k), f))) => if is_leaf(graph, f, E) {
+            return Some(number(graph, k))
+        },
+        (Abs, Some((Abs, _))) => return Some(i),
+        (Power(a), Some((Root(n), f))) => if a.to_f64() == n.to_f64() {
+            return Some(f)
+        },
+        (o, Some((g, f))) => if inverse(&o) == Some(g) {
+            return Some(f)
+        },
+        _ => ()
+    }
+    
+    let (l, r) = match graph.node(i) {
+        Mul(l, r) => (l, r),
+        _ => return None
+    };
+    match outer {
+        // exp(c * ln(f)) = f^c
+        Exp => {
+            let (c, g) = match (graph.constant(l), graph.constant(r)) {
+                (Some(c), None) => (c, r),
+                (None, Some(c)) => (c, l),
+                _ => return None
+            };
+            match applied(graph, g) {
+                Some((Ln, f)) => {
+                    let power = graph.intern(Leaf(Power(c)));
+                    Some(graph.intern(Compose(power, f)))
+                },
+                _ => None
             }
-        }
+        },
+        
+        // sin(-f) = -sin(f), tan(-f) = -tan(f), cos(-f) = cos(f)
+        Sin | Tan | Cos => {
+            let c = match graph.constant(l) {
+                Some(c) => c,
+                None => return None
+            };
+            if !c.is_negative() {
+                return None
+            }
+            let negated = negate(graph, &c, r);
+            let value = apply(graph, outer.clone(), negated);
+            if outer == Cos {
+                Some(value)
+            } else {
+                let minus_one = number(graph, Number::int(-1));
+                Some(graph.intern(Mul(minus_one, value)))
+            }
+        },
+        
+        _ => None
+    }
+}
+
+/// Applies the rules for operands that are the constants zero and one.
+fn identity(graph: &mut Graph, id: NodeId) -> Option<NodeId> {
+    let result = match graph.node(id) {
+        Leaf(Power(ref p)) if p.is_zero() => number(graph, Number::int(1)),
+        Plus(l, r) if graph.is_zero(l) => r,
+        Plus(l, r) if graph.is_zero(r) => l,
+        Minus(l, r) if graph.is_zero(l) => {
+            let minus_one = number(graph, Number::int(-1));
+            graph.intern(Mul(minus_one, r))
+        },
+        Minus(l, r) if graph.is_zero(r) => l,
+        Mul(l, r) if graph.is_one(l) => r,
+        Mul(l, r) if graph.is_one(r) => l,
+        Mul(l, r) if graph.is_zero(l) || graph.is_zero(r) => number(graph, Number::int(0)),
+        Div(l, r) if graph.is_one(r) => l,
+        Pow(_, e) if graph.is_zero(e) => number(graph, Number::int(1)),
+        Pow(b, e) if graph.is_one(e) => b,
+        
+        // sin(0) = tan(0) = sinh(0) = tanh(0) = 0, cos(0) = cosh(0) = 1
+        // exp(0) = 1, exp(1) = e, ln(1) = 0
+        Compose(o, i) => {
+            let value = match (leaf(graph, o), graph.is_zero(i), graph.is_one(i)) {
+                (Some(Sin), true, _) | (Some(Tan), true, _) |
+                (Some(Sinh), true, _) | (Some(Tanh), true, _) => Constant(Number::int(0)),
+                (Some(Cos), true, _) | (Some(Cosh), true, _) | (Some(Exp), true, _) => Constant(Number::int(1)),
+                (Some(Exp), _, true) => E,
+                (Some(Ln), _, true) => Constant(Number::int(0)),
+                _ => return None
+            };
+            graph.intern(Leaf(value))
+        },
+        
+        _ => return None
+    };
+    Some(result)
+}
+
+/// Evaluates sin, cos and tan at multiples of pi/2.
+fn special_angle(graph: &mut Graph, id: NodeId) -> Option<NodeId> {
+    let (outer, angle) = match applied(graph, id) {
+        Some(pair) => pair,
+        None => return None
+    };
+    
+    // The number of quarter turns.
+    let (sin, cos) = match pi_multiple(graph, angle).and_then(|k| (k * Number::int(2)).to_int()) {
+        Some(n) => match (n % 4 + 4) % 4 {
+            0 => (0, 1),
+            1 => (1, 0),
+            2 => (0, -1),
+            _ => (-1, 0)
+        },
+        None => return None
+    };
+    
+    let value = match outer {
+        Sin => sin,
+        Cos => cos,
+        Tan if sin == 0 => 0,
+        _ => return None
+    };
+    Some(number(graph, Number::int(value)))
+}
+
+/// Returns k for the node k * pi.
+fn pi_multiple(graph: &Graph, id: NodeId) -> Option<Number> {
+    match graph.node(id) {
+        Leaf(Pi) => Some(Number::int(1)),
+        Mul(k, p) if is_leaf(graph, p, Pi) => graph.constant(k),
+        Div(p, k) if is_leaf(graph, p, Pi) => graph.constant(k).map(|k| Number::int(1) / k),
+        _ => None
+    }
+}
+
+/// Returns cos(f)^2 for sin(f)^2 and the other way round.
+fn pythagorean_partner(graph: &mut Graph, term: NodeId) -> Option<NodeId> {
+    let (partner, f) = match square_of(graph, term) {
+        Some((Sin, f)) => (Cos, f),
+        Some((Cos, f)) => (Sin, f),
+        _ => return None
+    };
+    let inner = apply(graph, partner, f);
+    let square = graph.intern(Leaf(Power(Number::int(2))));
+    Some(graph.intern(Compose(square, inner)))
+}
+
+/// Removes a term from a sum, looking through the left operands of nested sums.
+fn remove_term(graph: &mut Graph, sum: NodeId, term: NodeId) -> Option<NodeId> {
+    if sum == term {
+        return Some(number(graph, Number::int(0)))
+    }
+    
+    match graph.node(sum) {
+        Plus(l, r) => if r == term {
+            Some(l)
+        } else {
+            match remove_term(graph, l, term) {
+                Some(rest) => Some(graph.intern(Plus(rest, r))),
+                None => None
+            }
+        },
+        Minus(l, r) => match remove_term(graph, l, term) {
+            Some(rest) => Some(graph.intern(Minus(rest, r))),
+            None => None
+        },
+        _ => None
+    }
+}
+
+/// Negates c * f for a negative c.
+fn negate(graph: &mut Graph, c: &Number, f: NodeId) -> NodeId {
+    if (-*c).is_one() {
+        f
+    } else {
+        let negated = number(graph, -*c);
+        graph.intern(Mul(negated, f))
     }
 }
+
+/// Returns the inverse of a trigonometric or hyperbolic function.
+fn inverse(f: &DiffFunc) -> Option<DiffFunc> {
+    let g = match *f {
+        Sin => Asin,
+        Cos => Acos,
+        Tan => Atan,
+        Cot => Acot,
+        Sinh => Asinh,
+        Cosh => Acosh,
+        Tanh => Atanh,
+        _ => return None
+    };
+    Some(g)
+}
+
+/// Returns the function of a leaf.
+fn leaf(graph: &Graph, id: NodeId) -> Option<DiffFunc> {
+    match graph.node(id) {
+        Leaf(f) => Some(f),
+        _ => None
+    }
+}
+
+/// Checks whether a node is the leaf f.
+fn is_leaf(graph: &Graph, id: NodeId, f: DiffFunc) -> bool {
+    graph.node(id) == Leaf(f)
+}
+
+/// Returns a for the leaf x^a.
+fn power_of(graph: &Graph, id: NodeId) -> Option<Number> {
+    match graph.node(id) {
+        Leaf(Power(a)) => Some(a),
+        _ => None
+    }
+}
+
+/// Returns a and f for x^a * f or f * x^a.
+fn split_power(graph: &Graph, id: NodeId) -> Option<(Number, NodeId)> {
+    match graph.node(id) {
+        Mul(l, r) => match (power_of(graph, l), power_of(graph, r)) {
+            (Some(a), _) => Some((a, r)),
+            (None, Some(a)) => Some((a, l)),
+            (None, None) => None
+        },
+        _ => None
+    }
+}
+
+/// Returns the outer function and the argument of a node like sin(f).
+fn applied(graph: &Graph, id: NodeId) -> Option<(DiffFunc, NodeId)> {
+    match graph.node(id) {
+        Compose(o, i) => leaf(graph, o).map(|f| (f, i)),
+        _ => None
+    }
+}
+
+/// Returns the outer function and f for a square like sin(f)^2.
+fn square_of(graph: &Graph, id: NodeId) -> Option<(DiffFunc, NodeId)> {
+    match graph.node(id) {
+        Compose(p, inner) if power_of(graph, p) == Some(Number::int(2)) => applied(graph, inner),
+        _ => None
+    }
+}
+
+/// Adds a constant.
+fn number(graph: &mut Graph, c: Number) -> NodeId {
+    graph.intern(Leaf(Constant(c)))
+}
+
+/// Adds f(arg) for a built-in function f.
+fn apply(graph: &mut Graph, f: DiffFunc, arg: NodeId) -> NodeId {
+    let outer = graph.intern(Leaf(f));
+    graph.intern(Compose(outer, arg))
+}
+
+/// Adds x^a * f.
+fn times_power(graph: &mut Graph, a: Number, f: NodeId) -> NodeId {
+    let power = graph.intern(Leaf(Power(a)));
+    graph.intern(Mul(power, f))
+}
@@ -0,0 +1,187 @@
+//! Root finding.
+//! Newton's method uses the symbolic derivative, and falls back to bisection where the
+//! derivative vanishes or a step would leave a bracket around the root.
+
+use func::DiffFunc;
+use simplify::derivative_n;
+
+/// The relative accuracy of a root.
+static TOLERANCE: f64 = 1e-12;
+
+/// The most steps taken before giving up.
+static MAX_ITERATIONS: uint = 200;
+
+/// The most times the search for a sign change doubles its width.
+static MAX_EXPANSIONS: uint = 60;
+
+/// The largest value at a root found by bracketing, relative to the values at the ends of the bracket.
+static RESIDUAL: f64 = 1e-6;
+
+/// A point where a function changes sign.
+pub enum Crossing {
+    /// The function is zero there.
+    Root(f64),
+    
+    /// The function jumps across zero there, like 1/x at 0.
+    Pole(f64)
+}
+
+/// Finds a root of a function of x with Newton's method, starting at a guess.
+pub fn newton(f: &DiffFunc, guess: f64) -> Result<f64, ~str> {
+    let df = derivative_n(f, "x", 1);
+    let mut x = guess;
+    for _ in range(0, MAX_ITERATIONS) {
+        let y = f.eval(x);
+        if !y.is_finite() {
+            return Err(format!("The function is not defined at {}.", x))
+        }
+        if y == 0.0 {
+            return Ok(x)
+        }
+        
+        // A flat point gives no direction, so look for a sign change around it instead.
+        let slope = df.eval(x);
+        if slope == 0.0 || !slope.is_finite() {
+            return match sign_change(f, x) {
+                Some((a, b)) => bracketed(f, a, b),
+                None => Err(format!("The derivative vanishes at {} and no sign change was found.", x))
+            }
+        }
+        
+        let next = x - y / slope;
+        if close(next, x) {
+            return if f.eval(next).is_finite() {
+                Ok(next)
+            } else {
+                Err(format!("The function is not defined at {}.", next))
+            }
+        }
+        x = next;
+    }
+    Err(format!("Newton's method did not converge from {}.", guess))
+}
+
+/// Finds a root of a function of x between a and b, where the function changes sign.
+/// A sign change where the function jumps across zero, like 1/x at 0, is a pole and an error.
+pub fn bracketed(f: &DiffFunc, a: f64, b: f64) -> Result<f64, ~str> {
+    match crossing(f, a, b) {
+        Ok(Root(x)) => Ok(x),
+        Ok(Pole(x)) => Err(format!("The sign change at {} is a pole, not a root.", x)),
+        Err(e) => Err(e)
+    }
+}
+
+/// Finds the point between a and b where a function of x changes sign.
+/// Newton steps are taken while they stay inside the bracket, bisection steps otherwise.
+pub fn crossing(f: &DiffFunc, a: f64, b: f64) -> Result<Crossing, ~str> {
+    let (fa, fb) = (f.eval(a), f.eval(b));
+    if fa == 0.0 {
+        return Ok(Root(a))
+    }
+    if fb == 0.0 {
+        return Ok(Root(b))
+    }
+    if !(fa * fb < 0.0) {
+        return Err(format!("The function does not change sign between {} and {}.", a, b))
+    }
+    
+    // Keep the function negative at lo and positive at hi.
+    let (mut lo, mut hi) = if fa < 0.0 { (a, b) } else { (b, a) };
+    let df = derivative_n(f, "x", 1);
+    let mut x = (a + b) / 2.0;
+    for _ in range(0, MAX_ITERATIONS) {
+        let y = f.eval(x);
+        if y == 0.0 {
+            return Ok(Root(x))
+        }
+        if y < 0.0 { lo = x } else { hi = x }
+        
+        let step = x - y / df.eval(x);
+        let next = if step.is_finite() && step > lo.min(&hi) && step < lo.max(&hi) {
+            step
+        } else {
+            (lo + hi) / 2.0
+        };
+        if close(next, x) || close(lo, hi) {
+            return Ok(settle(f, next, fa.abs().max(&fb.abs())))
+        }
+        x = next;
+    }
+    Err(format!("No root was found between {} and {}.", a, b))
+}
+
+/// Searches outwards from a point for an interval where the function changes sign.
+fn sign_change(f: &DiffFunc, x: f64) -> Option<(f64, f64)> {
+    let y = f.eval(x);
+    let mut step = x.abs().max(&1.0) * 1e-3;
+    for _ in range(0, MAX_EXPANSIONS) {
+        let (left, right) = (x - step, x + step);
+        if f.eval(left) * y < 0.0 {
+            return Some((left, x))
+        }
+        if f.eval(right) * y < 0.0 {
+            return Some((x, right))
+        }
+        step *= 2.0;
+    }
+    None
+}
+
+/// Decides whether the point a bracket has closed on is a root, where the function is finite and close to zero.
+fn settle(f: &DiffFunc, x: f64, scale: f64) -> Crossing {
+    let y = f.eval(x);
+    if y.is_finite() && y.abs() <= RESIDUAL * scale { Root(x) } else { Pole(x) }
+}
+
+/// Checks whether two points agree to the tolerance.
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TOLERANCE * a.abs().max(&1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use std::f64;
+    
+    use func::{DiffFunc, Cos, Constant, Power, Plus, Minus};
+    use number::Number;
+    use super::{newton, bracketed};
+    
+    /// x^2 + c
+    fn square_plus(c: int) -> DiffFunc {
+        Plus { left: ~Power(Number::int(2)), right: ~Constant(Number::int(c)) }
+    }
+    
+    #[test]
+    fn newton_converges() {
+        let root = newton(&square_plus(-2), 1.0).unwrap();
+        assert!((root - 2.0f64.sqrt()).abs() < 1e-10);
+    }
+    
+    #[test]
+    fn bracketed_converges() {
+        let root = bracketed(&Cos, 0.0, 3.0).unwrap();
+        assert!((root - f64::consts::PI / 2.0).abs() < 1e-10);
+        
+        // x^3 - x has three roots, the bracket picks one of them.
+        let cubic = Minus { left: ~Power(Number::int(3)), right: ~Power(Number::int(1)) };
+        let root = bracketed(&cubic, 0.5, 2.0).unwrap();
+        assert!((root - 1.0).abs() < 1e-10);
+    }
+    
+    #[test]
+    fn newton_fails_without_a_root() {
+        // The derivative vanishes at 0 and x^2 + 1 never changes sign.
+        assert!(newton(&square_plus(1), 0.0).is_err());
+    }
+    
+    #[test]
+    fn bracketed_needs_a_sign_change() {
+        assert!(bracketed(&square_plus(1), -1.0, 1.0).is_err());
+    }
+    
+    #[test]
+    fn bracketed_rejects_poles() {
+        // 1/x changes sign at 0 without a root.
+        assert!(bracketed(&Power(Number::int(-1)), -1.0, 2.0).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Statements understood by the calculator.
+
+use func::DiffFunc;
+
+/// A statement.
+pub enum Statement {
+    Derive   { func: ~DiffFunc, var: ~str, order: uint },
+    Evaluate { func: ~DiffFunc, var: ~str, order: uint, point: f64 },
+    Series   { func: ~DiffFunc, degree: uint, point: f64 },
+    Integrate { func: ~DiffFunc },
+    Quadrature { func: ~DiffFunc, from: f64, to: f64 },
+    Solve    { func: ~DiffFunc, guess: f64 },
+    SolveBetween { func: ~DiffFunc, from: f64, to: f64 },
+    Analyze  { func: ~DiffFunc, from: f64, to: f64 },
+    Define   { name: ~str, func: ~DiffFunc },
+    Assign   { name: ~str, value: ~DiffFunc }
+}
@@ -4,16 +4,23 @@ use std::io::buffered::BufferedReader;
 use std::str::from_char;
 use std::ascii::StrAsciiExt;
 
+use number;
+
 /// A token.
 #[deriving(Clone, ToStr, Eq)]
 pub enum Token {
-    Number(f64),
+    Number(number::Number),
     Id(~str),
     
     // Brackets
     OpenBracket,
     CloseBracket,
     
+    // Separators.
+    Comma,
+    Colon,
+    Assign,
+    
     // Operators.
     Plus,
     Minus,
@@ -21,19 +28,54 @@ pub enum Token {
     Div,
     Power,
     
+    // Relations.
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    
     // Built-in functions.
     Exp,
     Ln,
+    Log,
+    Log10,
+    Log2,
+    Sqrt,
+    Root,
     Sin,
     Cos,
     Tg,
-    Ctg
+    Ctg,
+    Asin,
+    Acos,
+    Atan,
+    Acot,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Abs,
+    Sign,
+    Piecewise,
+    
+    // Keywords.
+    At,
+    Wrt,
+    Taylor,
+    Integrate,
+    From,
+    To,
+    Solve,
+    Near,
+    Analyze
 }
 
 /// The tokenizer.
 pub struct Tokenizer<R> {
     priv reader: BufferedReader<R>,
-    priv token: Option<Token>,
+    priv tokens: ~[Token],
     priv ch: Option<char>,
     priv failed: bool
 }
@@ -55,7 +97,7 @@ impl<R: Reader> Tokenizer<R> {
     pub fn new(reader: R) -> Tokenizer<R> {
         Tokenizer::<R> { 
             reader: BufferedReader::<R>::new(reader), 
-            token: None,
+            tokens: ~[],
             ch: None,
             failed: false,
         }
@@ -68,20 +110,28 @@ impl<R: Reader> Tokenizer<R> {
 
     /// Returns and consumes the next token.
     pub fn take(&mut self) -> Option<Token> {
-        if self.token.is_none() {
-            self.token = self.read_token()
-        }
-        
-        self.token.take()
+        if self.fill(1) { Some(self.tokens.shift()) } else { None }
     }
     
     /// Returns the next token without consuming it.
     pub fn peek(&mut self) -> Option<Token> {
-        if self.token.is_none() {
-            self.token = self.read_token()
+        self.peek_at(0)
+    }
+    
+    /// Returns the token n places after the next one without consuming anything.
+    pub fn peek_at(&mut self, n: uint) -> Option<Token> {
+        if self.fill(n + 1) { Some(self.tokens[n].clone()) } else { None }
+    }
+    
+    /// Reads tokens until n are buffered, returning false if the stream ends first.
+    fn fill(&mut self, n: uint) -> bool {
+        while self.tokens.len() < n {
+            match self.read_token() {
+                Some(token) => self.tokens.push(token),
+                None => return false
+            }
         }
-        
-        self.token.clone()
+        true
     }
     
     /// Reads the next token.
@@ -100,21 +150,16 @@ impl<R: Reader> Tokenizer<R> {
             // Single character tokens.
             Some('(') => Some(OpenBracket),
             Some(')') => Some(CloseBracket),
+            Some(',') => Some(Comma),
+            Some(':') => Some(Colon),
+            Some('=') => Some(Assign),
             Some('+') => Some(Plus),
-            Some('-') => {
-                // Peek the next non-whitespace character.
-                while self.peek_char().map_or(false, |ch| ch.is_whitespace()) {
-                    self.take_char();
-                }
-                if self.peek_char().map_or(false, |ch| ch.is_digit()) {
-                    self.read_number('-')
-                } else {
-                    Some(Minus)
-                }
-            },
+            Some('-') => Some(Minus),
             Some('*') => Some(Mul),
             Some('/') => Some(Div),
             Some('^') => Some(Power),
+            Some('<') => Some(self.read_relation(Lt, Le)),
+            Some('>') => Some(self.read_relation(Gt, Ge)),
             
             // Multi character tokens.
             Some(ch)  => {
@@ -137,6 +182,16 @@ impl<R: Reader> Tokenizer<R> {
         }
     }
     
+    /// Reads a relation, which is strict unless followed by '='.
+    fn read_relation(&mut self, strict: Token, non_strict: Token) -> Token {
+        if self.peek_char() == Some('=') {
+            self.take_char();
+            non_strict
+        } else {
+            strict
+        }
+    }
+    
     /// Reads an number.
     fn read_number(&mut self, ch: char) -> Option<Token> {
         // Read all digits, dot characters or 'e' characters.
@@ -145,8 +200,8 @@ impl<R: Reader> Tokenizer<R> {
             s.push_char(self.take_char().unwrap());
         }
         
-        // Try to parse an float.
-        match from_str::<f64>(s) {
+        // Try to parse an exact integer or a float.
+        match from_str::<number::Number>(s) {
             Some(f) => Some(Number(f)),
             None    => self.handle_invalid_token(s)
         }
@@ -164,10 +219,52 @@ impl<R: Reader> Tokenizer<R> {
             // Match build-in functions.
             ~"exp" => Some(Exp),
             ~"ln"  => Some(Ln),
+            ~"log" => Some(Log),
+            ~"log10" => Some(Log10),
+            ~"log2"  => Some(Log2),
+            ~"sqrt"  => Some(Sqrt),
+            ~"root"  => Some(Root),
             ~"sin" => Some(Sin),
             ~"cos" => Some(Cos),
             ~"tg"  => Some(Tg),
+            ~"tan" => Some(Tg),
             ~"ctg" => Some(Ctg),
+            ~"cot" => Some(Ctg),
+            ~"asin"   => Some(Asin),
+            ~"arcsin" => Some(Asin),
+            ~"acos"   => Some(Acos),
+            ~"arccos" => Some(Acos),
+            ~"atan"   => Some(Atan),
+            ~"arctg"  => Some(Atan),
+            ~"arctan" => Some(Atan),
+            ~"acot"   => Some(Acot),
+            ~"arcctg" => Some(Acot),
+            ~"arccot" => Some(Acot),
+            ~"sinh"   => Some(Sinh),
+            ~"cosh"   => Some(Cosh),
+            ~"tanh"   => Some(Tanh),
+            ~"tgh"    => Some(Tanh),
+            ~"asinh"  => Some(Asinh),
+            ~"arsinh" => Some(Asinh),
+            ~"acosh"  => Some(Acosh),
+            ~"arcosh" => Some(Acosh),
+            ~"atanh"  => Some(Atanh),
+            ~"artanh" => Some(Atanh),
+            ~"abs"    => Some(Abs),
+            ~"sign"   => Some(Sign),
+            ~"sgn"    => Some(Sign),
+            ~"piecewise" => Some(Piecewise),
+            
+            // Match keywords.
+            ~"at"  => Some(At),
+            ~"wrt" => Some(Wrt),
+            ~"taylor" => Some(Taylor),
+            ~"integrate" => Some(Integrate),
+            ~"from" => Some(From),
+            ~"to"   => Some(To),
+            ~"solve" => Some(Solve),
+            ~"near"  => Some(Near),
+            ~"analyze" => Some(Analyze),
             
             // Otherwise it must be an identifier.
             _      => Some(Id(s))
@@ -214,3 +311,28 @@ impl<R: Reader> Tokenizer<R> {
         self.ch = self.reader.read_char()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::mem::MemReader;
+    use number::Number;
+    use super::{Tokenizer, Token, Id, Minus};
+    
+    fn tokens(s: &str) -> ~[Token] {
+        let mut tokenizer = Tokenizer::new(MemReader::new(s.as_bytes().to_owned()));
+        let mut result = ~[];
+        loop {
+            match tokenizer.take() {
+                Some(t) => result.push(t),
+                None => return result
+            }
+        }
+    }
+    
+    #[test]
+    fn minus_is_always_an_operator() {
+        let expected = ~[Id(~"x"), Minus, super::Number(Number::int(1))];
+        assert_eq!(tokens("x-1"), expected);
+        assert_eq!(tokens("x - 1"), expected);
+    }
+}